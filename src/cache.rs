@@ -0,0 +1,305 @@
+// cache.rs - Local persistence of resolved server versions
+
+use crate::events::{EventSink, ServerEvent};
+use crate::{install, resolver};
+use crate::{Endpoints, Platform, Quality, ServerConfig, ServerError, ServerInfo};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name of the file in `server_dir` holding the last successfully resolved `ServerInfo`
+const LAST_RESOLVED_FILE: &str = "server-info.json";
+
+/// The persisted resolution: the resolved `ServerInfo` and the request it answered
+#[derive(Serialize, Deserialize)]
+struct CacheRecord {
+    #[serde(default)]
+    request: serde_json::Value,
+    #[serde(flatten)]
+    info: ServerInfo,
+}
+
+/// The configuration that determines the outcome of a resolution
+#[derive(Serialize)]
+struct ResolutionRequest<'a> {
+    monaco_api_version: Option<&'a str>,
+    allow_prerelease: bool,
+    quality: Quality,
+    endpoints: &'a Endpoints,
+}
+
+impl<'a> ResolutionRequest<'a> {
+    fn new(config: &'a ServerConfig) -> Self {
+        Self {
+            monaco_api_version: config.monaco_api_version.as_deref(),
+            allow_prerelease: config.allow_prerelease,
+            quality: config.quality,
+            endpoints: &config.endpoints,
+        }
+    }
+
+    fn to_value(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+}
+
+/// Describes where the `ServerInfo` used by `ensure_server` came from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ResolutionSource {
+    /// Resolved by the configured `VersionResolver`.
+    #[default]
+    Resolver,
+    /// Reused from the resolution cache in `server_dir` within `ServerConfig::resolution_cache_ttl_secs`.
+    Cached,
+    /// Loaded from the last successful resolution persisted in `server_dir`.
+    LastResolved,
+    /// Derived from the most recently installed server in `server_dir`.
+    Installed,
+    /// Read from a local server archive installed with `VscodeServerManager::install_from_archive`.
+    Archive,
+}
+
+/// Returns the current time in seconds since the Unix epoch
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Persists `info` as the last successfully resolved server for `config`.
+pub(crate) fn save_last_resolved(config: &ServerConfig, info: &ServerInfo) -> Result<(), ServerError> {
+    let record = CacheRecord {
+        request: ResolutionRequest::new(config).to_value(),
+        info: info.clone(),
+    };
+    let json = serde_json::to_string_pretty(&record)
+        .map_err(|e| ServerError::Io(e.into()))?;
+    fs::create_dir_all(&config.server_dir)?;
+    
+    // Write through a temporary file so that concurrent readers never see a partial record
+    let path = config.server_dir.join(LAST_RESOLVED_FILE);
+    let temp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+    fs::write(&temp_path, json)?;
+    fs::rename(&temp_path, &path)?;
+    Ok(())
+}
+
+/// Returns the install directory of the last successfully resolved server in `server_dir`, if any.
+pub(crate) fn last_resolved_dir(server_dir: &Path) -> Option<PathBuf> {
+    load_record(server_dir).map(|record| server_dir.join(record.info.install_dir_name()))
+}
+
+/// Loads the last successfully resolved server from `server_dir`, if any.
+fn load_record(server_dir: &Path) -> Option<CacheRecord> {
+    let json = fs::read_to_string(server_dir.join(LAST_RESOLVED_FILE)).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+/// Returns the cached resolution if it is still fresh for `config` and `platform`.
+///
+/// The cache is bypassed when `ServerConfig::force_refresh` is set, the TTL is `0`, or
+/// `ServerConfig::frontend_dir` is set (the frontend files are the source of truth). A cached
+/// resolution is only reused if it was made for the same version request, quality and endpoints;
+/// a resolution made for another platform is reused for `platform`.
+pub(crate) fn load_fresh(config: &ServerConfig, platform: Platform) -> Option<ServerInfo> {
+    if config.force_refresh || config.resolution_cache_ttl_secs == 0 || config.frontend_dir.is_some() {
+        return None;
+    }
+
+    let record = load_record(&config.server_dir)?;
+    let age = unix_now().checked_sub(record.info.resolved_at)?;
+    if record.request != ResolutionRequest::new(config).to_value() || age >= config.resolution_cache_ttl_secs {
+        return None;
+    }
+
+    let mut info = if record.info.platform == platform {
+        record.info
+    } else {
+        record.info.for_platform(platform, &config.endpoints)
+    };
+    info.source = ResolutionSource::Cached;
+    Some(info)
+}
+
+/// Resolves the server to use without any network access.
+///
+/// Only installs whose `monaco_api_version` satisfies `ServerConfig::monaco_api_version` are
+/// considered at first: the last successfully resolved `ServerInfo` is preferred if its server is
+/// still installed for `platform` and the configured quality; otherwise the most recently modified
+/// matching install in `server_dir` is used. Servers in the `<commit>` directories of earlier
+/// versions of this crate count as installs of an unknown monaco-vscode-api version. If none satisfies a version range, the most recent
+/// install of that platform and quality is used instead and a `ServerEvent::Warning` is emitted;
+/// an exact version pin never falls back to another version.
+///
+/// # Errors
+///
+/// Returns `ServerError::VersionDetectionFailed` if the requested version is invalid or no usable
+/// server is installed.
+pub(crate) fn resolve_offline(
+    config: &ServerConfig,
+    platform: Platform,
+    events: &EventSink,
+) -> Result<ServerInfo, ServerError> {
+    let server_dir = &config.server_dir;
+    let requested = config.monaco_api_version.as_deref();
+    let req = requested.map(resolver::parse_version_req).transpose()?;
+    let satisfies = |info: &ServerInfo| {
+        req.as_ref().is_none_or(|req| {
+            resolver::parse_tag_version(&info.monaco_api_version)
+                .is_some_and(|version| resolver::matches_req(req, &version, config.allow_prerelease))
+        })
+    };
+
+    if let Some(CacheRecord { mut info, .. }) = load_record(server_dir)
+        && info.platform == platform
+        && info.quality == config.quality
+        && satisfies(&info)
+        && install::is_complete(&server_dir.join(info.install_dir_name()))
+    {
+        info.source = ResolutionSource::LastResolved;
+        return Ok(info);
+    }
+
+    let mut installed: Vec<(SystemTime, ServerInfo)> = fs::read_dir(server_dir)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let path = entry.path();
+            let info = match install::read_marker(&path) {
+                Some(record) if entry.file_name().to_string_lossy() == record.info.install_dir_name() => record.info,
+                Some(_) => return None,
+                // A server installed by an earlier version of this crate, adopted by `ensure_server`
+                None => install::legacy_server_info(&path, &config.endpoints)?,
+            };
+            if info.platform != platform || info.quality != config.quality {
+                return None;
+            }
+            let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+            Some((modified, info))
+        })
+        .collect();
+    installed.sort_by_key(|(modified, _)| Reverse(*modified));
+
+    let matching = installed.iter().position(|(_, info)| satisfies(info));
+    let selected = match (matching, &req, requested) {
+        (Some(index), _, _) => Some(installed.swap_remove(index).1),
+        (None, Some(req), Some(requested)) if !resolver::is_exact_pin(req) && !installed.is_empty() => {
+            let info = installed.swap_remove(0).1;
+            events.emit(ServerEvent::Warning(format!(
+                "Offline and no installed VSCode server matches monaco-vscode-api '{}', using the server for monaco-vscode-api {} instead",
+                requested, info.monaco_api_version
+            )));
+            Some(info)
+        }
+        _ => None,
+    };
+
+    match selected {
+        Some(mut info) => {
+            info.source = ResolutionSource::Installed;
+            info.resolved_at = 0;
+            Ok(info)
+        }
+        None => Err(ServerError::VersionDetectionFailed(match requested {
+            Some(requested) => format!(
+                "Offline and no installed {} VSCode server for monaco-vscode-api '{}' found in {}",
+                config.quality,
+                requested,
+                server_dir.display()
+            ),
+            None => format!(
+                "Offline and no installed {} VSCode server found in {}",
+                config.quality,
+                server_dir.display()
+            ),
+        })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A config caching resolutions in `server_dir` for 100 seconds
+    fn config(server_dir: &Path) -> ServerConfig {
+        ServerConfig {
+            server_dir: server_dir.to_path_buf(),
+            monaco_api_version: Some("^16.0".to_string()),
+            resolution_cache_ttl_secs: 100,
+            ..Default::default()
+        }
+    }
+
+    /// Persists a resolution for `config` made `age` seconds ago
+    fn save_resolution(config: &ServerConfig, age: u64) -> ServerInfo {
+        let info = ServerInfo {
+            monaco_api_version: "v16.1.1".to_string(),
+            vscode_commit: "863d2581ecda11849ec1d1fdc1f7e6d21c0e1b76".to_string(),
+            vscode_version: Some("1.98.2".to_string()),
+            vscode_ref: None,
+            platform: Platform::LinuxX64,
+            quality: config.quality,
+            download_url: config.endpoints.download_url("863d2581ecda11849ec1d1fdc1f7e6d21c0e1b76", Platform::LinuxX64, config.quality),
+            source: ResolutionSource::Resolver,
+            resolved_at: unix_now() - age,
+            downloaded_from: None,
+        };
+        save_last_resolved(config, &info).unwrap();
+        info
+    }
+
+    #[test]
+    fn reuses_resolution_within_ttl() {
+        let temp = tempfile::tempdir().unwrap();
+        let config = config(temp.path());
+        let saved = save_resolution(&config, 10);
+
+        let cached = load_fresh(&config, Platform::LinuxX64).unwrap();
+        assert_eq!(cached.source, ResolutionSource::Cached);
+        assert_eq!(cached.vscode_commit, saved.vscode_commit);
+
+        // A resolution for another platform is the same release
+        let derived = load_fresh(&config, Platform::Win32X64).unwrap();
+        assert_eq!(derived.platform, Platform::Win32X64);
+        assert_eq!(derived.download_url, saved.for_platform(Platform::Win32X64, &config.endpoints).download_url);
+    }
+
+    #[test]
+    fn expires_resolution_after_ttl() {
+        let temp = tempfile::tempdir().unwrap();
+        let config = config(temp.path());
+        save_resolution(&config, 100);
+        assert!(load_fresh(&config, Platform::LinuxX64).is_none());
+    }
+
+    #[test]
+    fn bypasses_cache_on_refresh() {
+        let temp = tempfile::tempdir().unwrap();
+        let config = config(temp.path());
+        save_resolution(&config, 10);
+
+        let forced = ServerConfig { force_refresh: true, ..config.clone() };
+        assert!(load_fresh(&forced, Platform::LinuxX64).is_none());
+        let uncached = ServerConfig { resolution_cache_ttl_secs: 0, ..config.clone() };
+        assert!(load_fresh(&uncached, Platform::LinuxX64).is_none());
+    }
+
+    #[test]
+    fn ignores_resolution_for_another_request() {
+        let temp = tempfile::tempdir().unwrap();
+        let config = config(temp.path());
+        save_resolution(&config, 10);
+
+        let other_version = ServerConfig { monaco_api_version: Some("^15.0".to_string()), ..config.clone() };
+        assert!(load_fresh(&other_version, Platform::LinuxX64).is_none());
+        let other_quality = ServerConfig { quality: Quality::Insider, ..config.clone() };
+        assert!(load_fresh(&other_quality, Platform::LinuxX64).is_none());
+        let mut other_endpoints = config.clone();
+        other_endpoints.endpoints.download_url_template = "https://mirror.example.com/{commit}/{flavor}/{quality}".to_string();
+        assert!(load_fresh(&other_endpoints, Platform::LinuxX64).is_none());
+    }
+}
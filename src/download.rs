@@ -1,263 +1,1246 @@
 // download.rs - Download functionality for VSCode server
 
-use crate::{Platform, ServerError, ServerInfo};
+use crate::events::{EventSink, ServerEvent};
+use crate::http::HttpClient;
+use crate::extract::{self, Extraction};
+use crate::install::{self, ProductJson};
+use crate::resolver;
+use crate::{Platform, ResolutionSource, ServerConfig, ServerError, ServerInfo};
 use futures_util::StreamExt;
-use serde::Deserialize;
-use std::fs::{self, File};
-use std::io::Write;
-use std::path::Path;
+use reqwest::StatusCode;
+use reqwest::header::{HeaderValue, CONTENT_RANGE, ETAG, IF_RANGE, LAST_MODIFIED, RANGE};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
 
-#[derive(Deserialize)]
-#[allow(dead_code)] // Fields are for deserialization structure, not all are directly used
-struct GitHubTag {
-    name: String,
-    commit: CommitInfo,
-}
-
-#[derive(Deserialize)]
-#[allow(dead_code)] // Fields are for deserialization structure, not all are directly used
-struct CommitInfo {
-    sha: String,
-}
-
-#[derive(Deserialize)]
-struct PackageJson {
-    config: ConfigSection,
-}
-
-#[derive(Deserialize)]
-struct ConfigSection {
-    vscode: VscodeConfig,
-}
-
-#[derive(Deserialize)]
-struct VscodeConfig {
-    commit: String,
-    // version: String, // Example: if we also wanted the version string like "1.98.2"
-    // #[serde(rename = "ref")] // Example: if we wanted the field named "ref"
-    // ref_field: String,
-}
+/// How many times larger an extracted server is than its compressed archive, at most.
+/// Used to estimate the disk space an install needs.
+const EXTRACTION_FACTOR: u64 = 4;
 
-/// Detects the latest compatible VSCode server version information.
+/// Downloads and extracts the VSCode server based on the provided `ServerInfo` into a
+/// subdirectory of `target_dir`, using the default `ServerConfig` settings otherwise.
 ///
-/// This function queries the GitHub API for the latest tag of the `CodinGame/monaco-vscode-api`
-/// repository. It then fetches the `package.json` for that tag to extract the
-/// corresponding VSCode commit SHA (`vscodeRef`).
-///
-/// Finally, it constructs a `ServerInfo` struct containing the `monaco-vscode-api` version,
-/// the VSCode commit SHA, the current platform, and the direct download URL for the server.
+/// Use `download_server_with_events` to configure checksums, mirrors and retries, to receive
+/// progress events or to learn which URL the archive was downloaded from.
 ///
 /// # Errors
 ///
-/// Returns `ServerError` if:
-/// - Network requests fail (`ServerError::Network`).
-/// - No tags are found for `monaco-vscode-api` (`ServerError::VersionDetectionFailed`).
-/// - `package.json` cannot be fetched or parsed (`ServerError::Network`, `ServerError::VersionDetectionFailed`).
-/// - The current platform is unsupported (`ServerError::UnsupportedPlatform`).
-pub async fn detect_version() -> Result<ServerInfo, ServerError> {
-    let client = create_client()?;
-    
-    // Get latest monaco-vscode-api tag
-    let tags: Vec<GitHubTag> = client
-        .get("https://api.github.com/repos/CodinGame/monaco-vscode-api/tags")
-        .send()
-        .await?
-        .json()
-        .await?;
-    
-    let latest_tag = tags.first()
-        .ok_or_else(|| ServerError::VersionDetectionFailed(
-            "No tags found in monaco-vscode-api repository".to_string()
-        ))?;
-    
-    // Get VSCode commit from package.json
-    let package_json: PackageJson = client
-        .get(format!(
-            "https://raw.githubusercontent.com/CodinGame/monaco-vscode-api/{}/package.json",
-            latest_tag.name
-        ))
-        .send()
-        .await?
-        .json()
-        .await?;
-    
-    let platform = Platform::current()
-        .map_err(ServerError::UnsupportedPlatform)?;
-    
-    let vscode_commit_sha = &package_json.config.vscode.commit;
-    let download_url = format!(
-        "https://update.code.visualstudio.com/commit:{}/{}/{}",
-        vscode_commit_sha,
-        platform.server_flavor(),
-        platform.url_suffix()
-    );
-    
-    Ok(ServerInfo {
-        monaco_api_version: latest_tag.name.clone(),
-        vscode_commit: vscode_commit_sha.clone(),
-        platform,
-        download_url,
-    })
+/// See `download_server_with_events`.
+pub async fn download_server(info: &ServerInfo, target_dir: &Path) -> Result<(), ServerError> {
+    let config = ServerConfig { server_dir: target_dir.to_path_buf(), ..ServerConfig::default() };
+    download_server_with_events(info, &config, &EventSink::default()).await?;
+    Ok(())
 }
 
-/// Downloads and extracts the VSCode server based on the provided `ServerInfo`.
+/// Downloads and extracts the VSCode server based on the provided `ServerInfo`, reporting
+/// `ServerEvent`s (download and extraction progress, retries according to `config.retry`) to `events`.
 ///
-/// The server is downloaded from `info.download_url` and extracted into a subdirectory
-/// named after `info.vscode_commit` within the `target_dir`.
+/// The server is downloaded from the mirrors in `config.mirrors`, tried in order, and then
+/// `info.download_url`. A mirror failing with an HTTP error or a checksum mismatch is skipped
+/// with a `ServerEvent::MirrorFailed`. The archive is extracted into a subdirectory
+/// named after `info.install_dir_name()` (the VSCode commit and platform, plus the quality
+/// unless it is stable) within `config.server_dir`. `info.platform` need not be the host platform.
 /// If the server directory for the specific commit already exists, the download is skipped.
+/// An interrupted download is resumed on the next call when the server supports range requests.
+///
+/// The SHA-256 hash of the archive is computed while streaming and compared with the expected
+/// hash: `config.expected_sha256`, a matching entry of `config.checksum_file`, or otherwise the
+/// checksum published by the update service's metadata API (`Endpoints::checksum_url_template`).
+/// The verified hash is recorded in `<install dir>.sha256` next to the install; no such file is
+/// written when no expected hash is known and the archive could not be verified.
+///
+/// Transient failures are retried according to `config.retry`; an interrupted transfer
+/// resumes where it stopped.
+///
+/// The install is complete once a marker file has been written into it as the last step. An
+/// install directory without the marker, left by an interrupted install or a partial copy, is
+/// removed and installed again, along with staging directories and archives of earlier attempts.
+/// A server installed by an earlier version of this crate is adopted instead if it contains the
+/// launcher and a `product.json` naming the expected commit.
+///
+/// Installs are safe to run concurrently, also across processes: an advisory lock on
+/// `<install dir>.lock` lets one caller install a given server while the others wait and reuse
+/// it. The archive is extracted into a staging directory unique to the attempt and moved into
+/// place with a single rename, so the install directory never appears partially extracted.
+///
+/// The archive is written with `tokio::fs` and extracted on a blocking thread, so the async
+/// runtime is never stalled. Dropping the returned future cancels the install: the extraction
+/// stops after its current entry and the staging directory is removed.
+///
+/// Before anything is written, `server_dir` is checked to be writable, and once the archive's
+/// `Content-Length` is known, the free disk space is checked against the archive plus its
+/// estimated extracted size.
 ///
 /// # Arguments
 ///
 /// * `info` - A `ServerInfo` struct containing details about the server version to download.
-/// * `target_dir` - The base directory where the server (and its version-specific subdirectory)
-///                  will be placed. This directory will be created if it doesn't exist.
+/// * `config` - The configuration providing the base directory (`server_dir`) where the server
+///   (and its version-specific subdirectory) will be placed, and the expected checksum.
+///   `server_dir` will be created if it doesn't exist.
+/// * `events` - The sink progress events are reported to.
 ///
 /// # Errors
 ///
 /// Returns `ServerError` if:
-/// - `target_dir` cannot be created (`ServerError::Io`).
+/// - `server_dir` cannot be created or written to (`ServerError::NotWritable`).
+/// - The disk lacks space for the archive and the extracted server (`ServerError::InsufficientSpace`).
 /// - The download fails (e.g., network error, HTTP error status) (`ServerError::Network`, `ServerError::DownloadFailed`).
+/// - The archive does not match the expected SHA-256 hash (`ServerError::ChecksumMismatch`).
 /// - The archive extraction fails (`ServerError::ExtractionFailed`, `ServerError::Io`).
-pub async fn download_server(info: &ServerInfo, target_dir: &Path) -> Result<(), ServerError> {
-    let client = create_client()?;
+///
+/// Download errors are only returned once every mirror has failed.
+///
+/// Returns the URL the archive was downloaded from, or `None` if the server was already installed.
+pub async fn download_server_with_events(
+    info: &ServerInfo,
+    config: &ServerConfig,
+    events: &EventSink,
+) -> Result<Option<String>, ServerError> {
+    let http = HttpClient::new(config, events)?;
+    let target_dir = &config.server_dir;
     
     // Create target directory
-    fs::create_dir_all(target_dir)?;
+    create_server_dir(target_dir).await?;
     
-    let server_dir = target_dir.join(&info.vscode_commit);
-    if server_dir.exists() {
-        return Ok(());
+    let server_dir = target_dir.join(info.install_dir_name());
+    if install::is_complete(&server_dir) {
+        events.emit(ServerEvent::Installed { path: server_dir });
+        return Ok(None);
     }
+    check_writable(target_dir).await?;
     
-    println!("Downloading VSCode server...");
-    println!("Version: {} ({})", info.monaco_api_version, info.vscode_commit);
-    println!("URL: {}", info.download_url);
+    // Only one process installs a given server at a time; the others wait and reuse its result
+    let _lock = lock_install(&with_suffix(&server_dir, ".lock"), events).await?;
+    if install::is_complete(&server_dir) {
+        events.emit(ServerEvent::Installed { path: server_dir });
+        return Ok(None);
+    }
     
-    // Download the archive
-    let response = client.get(&info.download_url).send().await?;
+    // Reuse a server installed by an earlier version of this crate
+    let migration = blocking({
+        let (server_dir, info) = (server_dir.clone(), info.clone());
+        move || install::adopt_legacy(&server_dir, &info)
+    }).await?;
+    if !migration.removed.is_empty() {
+        events.emit(ServerEvent::IncompleteInstallRemoved { paths: migration.removed });
+    }
+    if let Some(from) = migration.adopted {
+        events.emit(ServerEvent::LegacyInstallAdopted { from, path: server_dir.clone() });
+        events.emit(ServerEvent::Installed { path: server_dir });
+        return Ok(None);
+    }
     
-    if !response.status().is_success() {
-        return Err(ServerError::DownloadFailed(
-            format!("Failed to download: {}", response.status())
+    // Clean up after an earlier attempt that was interrupted
+    let removed = blocking({
+        let server_dir = server_dir.clone();
+        move || install::remove_incomplete(&server_dir)
+    }).await?;
+    if !removed.is_empty() {
+        events.emit(ServerEvent::IncompleteInstallRemoved { paths: removed });
+    }
+    
+    let expected_sha256 = expected_checksum(&http, config, info).await?;
+    if expected_sha256.is_none() {
+        events.emit(ServerEvent::Warning(
+            "No published checksum found, the archive will not be verified".to_string()
         ));
     }
     
-    let total_size = response.content_length().unwrap_or(0);
     let archive_name = if info.platform.uses_zip() {
-        format!("vscode-server-{}.zip", info.vscode_commit)
+        format!("vscode-server-{}.zip", info.install_dir_name())
     } else {
-        format!("vscode-server-{}.tar.gz", info.vscode_commit)
+        format!("vscode-server-{}.tar.gz", info.install_dir_name())
     };
     
-    let archive_path = target_dir.join(archive_name);
-    let mut file = File::create(&archive_path)?;
-    let mut downloaded = 0u64;
-    let mut stream = response.bytes_stream();
+    let archive_path = target_dir.join(&archive_name);
+    let staging = StagingDir::new(&server_dir);
+    let staging_dir = staging.path();
+    // Zip archives cannot be read before their central directory at the end has arrived
+    let streaming = config.streaming_extraction && !info.platform.uses_zip();
+    if streaming {
+        events.emit(ServerEvent::ExtractionStarted { archive: archive_path.clone() });
+    }
     
-    while let Some(chunk) = stream.next().await {
-        let chunk = chunk?;
-        file.write_all(&chunk)?;
-        downloaded += chunk.len() as u64;
+    let urls = download_urls(config, info);
+    let mut downloaded = None;
+    for (index, url) in urls.iter().enumerate() {
+        let attempt = if streaming {
+            fetch_and_unpack(&http, url, staging_dir, expected_sha256.as_deref(), events).await
+        } else {
+            fetch_archive(&http, url, &archive_path, expected_sha256.as_deref()).await
+        };
+        match attempt {
+            Ok(sha256) => {
+                downloaded = Some((url.clone(), sha256));
+                break;
+            }
+            // Fall through to the next mirror unless this was the last one
+            Err(e @ (ServerError::Network(_) | ServerError::DownloadFailed(_) | ServerError::ChecksumMismatch { .. }))
+                if index + 1 < urls.len() =>
+            {
+                events.emit(ServerEvent::MirrorFailed { url: url.clone(), reason: e.to_string() });
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    let Some((download_url, actual_sha256)) = downloaded else {
+        return Err(ServerError::DownloadFailed("No download URL configured".to_string()));
+    };
+    if expected_sha256.is_some() {
+        events.emit(ServerEvent::ChecksumVerified { sha256: actual_sha256.clone() });
+    }
+    
+    if streaming {
+        // The archive was unpacked while downloading; move it into place now that it is verified
+        blocking({
+            let (staging_dir, server_dir) = (staging_dir.to_path_buf(), server_dir.clone());
+            move || extract::install_unpacked(&staging_dir, &server_dir)
+        }).await?;
         
-        if total_size > 0 {
-            let progress = (downloaded as f64 / total_size as f64) * 100.0;
-            print!("\rDownloading: {:.1}%", progress);
-            let _ = std::io::stdout().flush();
+        // Drop a partial download left by an earlier attempt without streaming
+        let _ = tokio::fs::remove_file(with_suffix(&archive_path, ".part")).await;
+        let _ = tokio::fs::remove_file(with_suffix(&archive_path, ".part.json")).await;
+    } else {
+        // Extract the archive
+        events.emit(ServerEvent::ExtractionStarted { archive: archive_path.clone() });
+        extract_archive(&archive_path, staging_dir, &server_dir, info.platform, events).await?;
+        
+        // Clean up archive
+        tokio::fs::remove_file(&archive_path).await?;
+    }
+    events.emit(ServerEvent::ExtractionFinished { path: server_dir.clone() });
+    
+    // Record the verified hash next to the install
+    if expected_sha256.is_some() {
+        tokio::fs::write(
+            with_suffix(&server_dir, ".sha256"),
+            format!("{}  {}\n", actual_sha256, archive_name),
+        ).await?;
+    }
+    
+    // Mark the install as complete only once everything else is in place
+    let installed = ServerInfo { downloaded_from: Some(download_url.clone()), ..info.clone() };
+    blocking({
+        let server_dir = server_dir.clone();
+        move || install::write_marker(&server_dir, &installed, Some(&actual_sha256))
+    }).await?;
+    
+    events.emit(ServerEvent::Installed { path: server_dir });
+    Ok(Some(download_url))
+}
+
+/// Installs the server contained in a local `.tar.gz` or `.zip` archive into `config.server_dir`.
+///
+/// The archive goes through the same extraction, normalization and verification as in
+/// `download_server_with_events`. Its VSCode commit and quality are read from the server's `product.json`
+/// and its platform from the archive's top-level directory (e.g. `vscode-server-linux-x64`);
+/// zip archives without such a directory are taken to be Windows servers. If `expected_commit`
+/// is given, it must match the archive, and it is used when the archive has no `product.json`.
+/// Either way the commit must be a full 40-character SHA.
+///
+/// `config.expected_sha256` is checked before anything is extracted, while an entry of
+/// `config.checksum_file` can only be looked up once the commit and platform are known.
+///
+/// Returns information about the installed server, with `ResolutionSource::Archive` as its source.
+pub(crate) async fn install_from_archive(
+    archive_path: &Path,
+    expected_commit: Option<&str>,
+    config: &ServerConfig,
+    events: &EventSink,
+) -> Result<ServerInfo, ServerError> {
+    if let Some(commit) = expected_commit {
+        check_archive_commit(commit)?;
+    }
+    let target_dir = &config.server_dir;
+    create_server_dir(target_dir).await?;
+    check_writable(target_dir).await?;
+    let archive_size = tokio::fs::metadata(archive_path).await?.len();
+    check_free_space(target_dir, archive_size.saturating_mul(EXTRACTION_FACTOR))?;
+    
+    let actual_sha256 = hex_encode(&hash_file(archive_path).await?.finalize());
+    
+    // An explicit checksum does not depend on the archive's contents, so check it before extracting
+    if let Some(expected) = &config.expected_sha256 {
+        verify_checksum(expected, &actual_sha256, events)?;
+    }
+    
+    // Where the server is installed depends on the archive's contents, so unpack it first
+    let archive_name = archive_path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let staging = StagingDir::new(&target_dir.join(&archive_name));
+    events.emit(ServerEvent::ExtractionStarted { archive: archive_path.to_path_buf() });
+    let archive = archive_path.to_path_buf();
+    let (root, zip) = run_extraction(staging.path(), events, move |staging_dir, extraction| {
+        let zip = is_zip(&archive)?;
+        extract::unpack_archive(&archive, staging_dir, zip, extraction)?;
+        Ok((extract::unpacked_root(staging_dir)?, zip))
+    }).await?;
+    let info = archive_server_info(&root, zip, expected_commit, config)?;
+    
+    // A checksum file is keyed by commit and platform, which are only known now
+    let verified = match (&config.expected_sha256, checksum_file_entry(config, &info)?) {
+        (Some(_), _) => true,
+        (None, Some(expected)) => {
+            verify_checksum(&expected, &actual_sha256, events)?;
+            true
         }
+        (None, None) => false,
+    };
+    
+    let server_dir = target_dir.join(info.install_dir_name());
+    let _lock = lock_install(&with_suffix(&server_dir, ".lock"), events).await?;
+    if install::is_complete(&server_dir) {
+        events.emit(ServerEvent::Installed { path: server_dir });
+        return Ok(info);
+    }
+    let removed = blocking({
+        let server_dir = server_dir.clone();
+        move || install::remove_incomplete(&server_dir)
+    }).await?;
+    if !removed.is_empty() {
+        events.emit(ServerEvent::IncompleteInstallRemoved { paths: removed });
+    }
+    
+    tokio::fs::rename(&root, &server_dir).await?;
+    events.emit(ServerEvent::ExtractionFinished { path: server_dir.clone() });
+    
+    if verified {
+        tokio::fs::write(
+            with_suffix(&server_dir, ".sha256"),
+            format!("{}  {}\n", actual_sha256, archive_name),
+        ).await?;
     }
-    println!("\nDownload complete!");
+    blocking({
+        let (server_dir, info) = (server_dir.clone(), info.clone());
+        move || install::write_marker(&server_dir, &info, Some(&actual_sha256))
+    }).await?;
+    
+    events.emit(ServerEvent::Installed { path: server_dir });
+    Ok(info)
+}
+
+/// Checks whether the archive at `archive_path` is a zip file, rather than a tar.gz
+fn is_zip(archive_path: &Path) -> Result<bool, ServerError> {
+    let mut magic = [0; 4];
+    let len = File::open(archive_path)?.read(&mut magic)?;
+    Ok(magic[..len] == *b"PK\x03\x04")
+}
+
+/// Checks that the archive hash `actual` matches `expected`
+fn verify_checksum(expected: &str, actual: &str, events: &EventSink) -> Result<(), ServerError> {
+    let expected = expected.trim();
+    if !expected.eq_ignore_ascii_case(actual) {
+        return Err(ServerError::ChecksumMismatch { expected: expected.to_string(), actual: actual.to_string() });
+    }
+    events.emit(ServerEvent::ChecksumVerified { sha256: actual.to_string() });
+    Ok(())
+}
+
+/// Describes the server unpacked into `root` from a local archive.
+///
+/// The platform is read from the name of the archive's top-level directory; only zip archives,
+/// which are built for Windows alone, may do without it.
+fn archive_server_info(
+    root: &Path,
+    zip: bool,
+    expected_commit: Option<&str>,
+    config: &ServerConfig,
+) -> Result<ServerInfo, ServerError> {
+    let product_path = root.join("product.json");
+    let product = match fs::read_to_string(&product_path) {
+        Ok(json) => serde_json::from_str::<ProductJson>(&json)
+            .map_err(|e| ServerError::ExtractionFailed(format!("Invalid {}: {}", product_path.display(), e)))?,
+        Err(_) => ProductJson { commit: None, version: None, quality: None },
+    };
     
-    // Extract the archive
-    println!("Extracting server...");
-    extract_archive(&archive_path, &server_dir, info.platform)?;
+    let vscode_commit = match (product.commit, expected_commit) {
+        (Some(commit), Some(expected)) if !commit.eq_ignore_ascii_case(expected) => {
+            return Err(ServerError::ExtractionFailed(format!(
+                "Archive contains VSCode commit {}, expected {}", commit, expected
+            )));
+        }
+        (Some(commit), _) => commit,
+        (None, Some(expected)) => expected.to_string(),
+        (None, None) => {
+            return Err(ServerError::ExtractionFailed(
+                "Archive has no product.json naming its VSCode commit; pass the expected commit".to_string()
+            ));
+        }
+    };
+    check_archive_commit(&vscode_commit)?;
     
-    // Clean up archive
-    fs::remove_file(archive_path)?;
+    let platform = match root.file_name()
+        .and_then(|name| name.to_str()?.strip_prefix("vscode-server-")?.parse().ok())
+    {
+        Some(platform) => platform,
+        None if zip => Platform::Win32X64,
+        None => {
+            return Err(ServerError::ExtractionFailed(format!(
+                "Cannot tell the platform of the archive: its top-level directory {} is not named vscode-server-<platform>",
+                root.file_name().unwrap_or_default().to_string_lossy()
+            )));
+        }
+    };
+    let quality = product.quality
+        .and_then(|quality| quality.parse().ok())
+        .unwrap_or(config.quality);
     
-    println!("Server ready at: {}", server_dir.display());
+    Ok(ServerInfo {
+        monaco_api_version: "unknown".to_string(),
+        download_url: config.endpoints.download_url(&vscode_commit, platform, quality),
+        vscode_commit,
+        vscode_version: product.version,
+        vscode_ref: None,
+        platform,
+        quality,
+        source: ResolutionSource::Archive,
+        resolved_at: 0,
+        downloaded_from: None,
+    })
+}
+
+/// Checks that the commit of an archive install is a full commit SHA, as it names the install directory
+fn check_archive_commit(commit: &str) -> Result<(), ServerError> {
+    if !resolver::is_commit_sha(commit) {
+        return Err(ServerError::ExtractionFailed(format!(
+            "Invalid VSCode commit {:?}: expected a 40-character hexadecimal SHA", commit
+        )));
+    }
     Ok(())
 }
 
-/// Creates an HTTP client
-fn create_client() -> Result<reqwest::Client, ServerError> {
-    reqwest::Client::builder()
-        .user_agent("vscode-server-backend")
-        .timeout(std::time::Duration::from_secs(300))
-        .build()
-        .map_err(Into::into)
+/// Returns the URLs to download the archive described by `info` from, in order of preference:
+/// the expanded `config.mirrors`, then `info.download_url`.
+fn download_urls(config: &ServerConfig, info: &ServerInfo) -> Vec<String> {
+    let mut urls: Vec<String> = config.mirrors.iter()
+        .map(|template| config.endpoints.expand(template, &info.vscode_commit, info.platform, info.quality))
+        .collect();
+    if !urls.contains(&info.download_url) {
+        urls.push(info.download_url.clone());
+    }
+    urls
 }
 
-/// Extracts the downloaded archive
-fn extract_archive(archive_path: &Path, target_dir: &Path, platform: Platform) -> Result<(), ServerError> {
-    if platform.uses_zip() {
-        extract_zip(archive_path, target_dir)
-    } else {
-        extract_tar_gz(archive_path, target_dir)
-    }
-}
-
-/// Extracts a tar.gz archive
-fn extract_tar_gz(archive_path: &Path, target_dir: &Path) -> Result<(), ServerError> {
-    use flate2::read::GzDecoder;
-    use tar::Archive;
-    
-    let file = File::open(archive_path)?;
-    let tar = GzDecoder::new(file);
-    let mut archive = Archive::new(tar);
-    
-    // Create a temporary directory
-    let temp_dir = target_dir.with_extension("tmp");
-    fs::create_dir_all(&temp_dir)?;
-    
-    // Extract to temp directory
-    archive.unpack(&temp_dir)
-        .map_err(|e| ServerError::ExtractionFailed(e.to_string()))?;
-    
-// Find the vscode directory and rename it
-     let entries = fs::read_dir(&temp_dir)?;
-    let mut found_dirs = Vec::new();
-     for entry in entries {
-         let entry = entry?;
-         if entry.path().is_dir() {
-            found_dirs.push(entry.path());
-         }
-     }
-     
-    if found_dirs.len() != 1 {
-        return Err(ServerError::ExtractionFailed(
-            format!("Expected exactly one directory in archive, found {}", found_dirs.len())
-        ));
+/// Downloads the archive from `url` to `archive_path`, retrying interrupted transfers, and
+/// checks it against `expected_sha256`.
+///
+/// Returns the hex-encoded SHA-256 hash of the archive. On a mismatch, the archive is removed.
+async fn fetch_archive(
+    http: &HttpClient,
+    url: &str,
+    archive_path: &Path,
+    expected_sha256: Option<&str>,
+) -> Result<String, ServerError> {
+    let actual_sha256 = http.retry(url, || download_archive(http, url, archive_path)).await?;
+    
+    if let Some(expected) = expected_sha256
+        && !expected.eq_ignore_ascii_case(&actual_sha256)
+    {
+        tokio::fs::remove_file(archive_path).await?;
+        return Err(ServerError::ChecksumMismatch { expected: expected.to_string(), actual: actual_sha256 });
+    }
+    Ok(actual_sha256)
+}
+
+/// The parts of the update service's version metadata used for verification
+#[derive(Deserialize)]
+struct UpdateMetadata {
+    sha256hash: Option<String>,
+}
+
+/// Determines the expected SHA-256 hash of the server archive described by `info`.
+///
+/// `config.expected_sha256` takes precedence, followed by the `<commit>/<flavor>/<quality>`
+/// entry of `config.checksum_file`, and finally the update service's metadata API. A failed
+/// metadata lookup is not an error: `None` is returned and the archive is not verified.
+async fn expected_checksum(
+    http: &HttpClient,
+    config: &ServerConfig,
+    info: &ServerInfo,
+) -> Result<Option<String>, ServerError> {
+    if let Some(expected) = configured_checksum(config, info)? {
+        return Ok(Some(expected));
     }
     
-    fs::rename(&found_dirs[0], target_dir)?;
+    let Some(template) = &config.endpoints.checksum_url_template else {
+        return Ok(None);
+    };
+    let url = config.endpoints.expand(template, &info.vscode_commit, info.platform, info.quality);
+    let metadata = match http.send(|client| client.get(&url)).await {
+        Ok(response) if response.status().is_success() => response.json::<UpdateMetadata>().await.ok(),
+        _ => None,
+    };
+    Ok(metadata.and_then(|metadata| metadata.sha256hash))
+}
+
+/// Determines the expected SHA-256 hash of the archive described by `info` from the
+/// configuration alone: `config.expected_sha256`, or else the entry of `config.checksum_file`.
+fn configured_checksum(config: &ServerConfig, info: &ServerInfo) -> Result<Option<String>, ServerError> {
+    if let Some(expected) = &config.expected_sha256 {
+        return Ok(Some(expected.trim().to_string()));
+    }
+    checksum_file_entry(config, info)
+}
+
+/// Looks up the hash of the archive described by `info` in `config.checksum_file`, if set
+fn checksum_file_entry(config: &ServerConfig, info: &ServerInfo) -> Result<Option<String>, ServerError> {
+    if let Some(checksum_file) = &config.checksum_file {
+        let key = format!("{}/{}/{}", info.vscode_commit, info.platform.server_flavor(), info.quality);
+        let contents = fs::read_to_string(checksum_file)?;
+        let entry = contents.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| line.split_once(char::is_whitespace))
+            .find(|(_, name)| name.trim().trim_start_matches('*') == key);
+        return match entry {
+            Some((hash, _)) => Ok(Some(hash.to_string())),
+            None => Err(ServerError::DownloadFailed(format!(
+                "No checksum for {} in {}",
+                key,
+                checksum_file.display()
+            ))),
+        };
+    }
+    Ok(None)
+}
+
+/// Downloads the tar.gz archive from `url`, unpacking it into `staging_dir` on a blocking task
+/// as the data arrives, and checks it against `expected_sha256`.
+///
+/// The archive is never written to disk. An interrupted transfer restarts from the beginning.
+/// Returns the hex-encoded SHA-256 hash of the archive. On a mismatch, `staging_dir` is removed.
+async fn fetch_and_unpack(
+    http: &HttpClient,
+    url: &str,
+    staging_dir: &Path,
+    expected_sha256: Option<&str>,
+    events: &EventSink,
+) -> Result<String, ServerError> {
+    let actual_sha256 = http.retry(url, || stream_archive(http, url, staging_dir, events)).await?;
     
-     // Clean up temp directory
-    if let Err(e) = fs::remove_dir_all(&temp_dir) {
-        eprintln!("Warning: Failed to clean up temp directory: {}", e);
+    if let Some(expected) = expected_sha256
+        && !expected.eq_ignore_ascii_case(&actual_sha256)
+    {
+        tokio::fs::remove_dir_all(staging_dir).await?;
+        return Err(ServerError::ChecksumMismatch { expected: expected.to_string(), actual: actual_sha256 });
     }
+    Ok(actual_sha256)
+}
+
+/// Streams the response body of `url` through a `GzDecoder` into a `tar::Archive` unpacked
+/// into `staging_dir`, hashing it on the way.
+///
+/// Returns the hex-encoded SHA-256 hash of the archive.
+async fn stream_archive(
+    http: &HttpClient,
+    url: &str,
+    staging_dir: &Path,
+    events: &EventSink,
+) -> Result<String, ServerError> {
+    if tokio::fs::try_exists(staging_dir).await? {
+        tokio::fs::remove_dir_all(staging_dir).await?;
+    }
+    tokio::fs::create_dir_all(staging_dir).await?;
     
-    Ok(())
+    let response = http.send(|client| client.get(url)).await?;
+    if !response.status().is_success() {
+        return Err(ServerError::DownloadFailed(
+            format!("Failed to download: {}", response.status())
+        ));
+    }
+    let total_size = response.content_length();
+    if let (Some(total_size), Some(dir)) = (total_size, staging_dir.parent()) {
+        check_free_space(dir, total_size.saturating_mul(EXTRACTION_FACTOR))?;
+    }
+    http.events().emit(ServerEvent::DownloadStarted {
+        url: url.to_string(),
+        resumed_from: 0,
+        total_bytes: total_size,
+    });
+    
+    let (sender, receiver) = tokio::sync::mpsc::channel(16);
+    let extraction = run_extraction(staging_dir, events, move |staging_dir, extraction| {
+        extract::unpack_tar_gz(ChannelReader::new(receiver), staging_dir, extraction)
+    });
+    
+    let mut hasher = Sha256::new();
+    let mut downloaded = 0;
+    let mut stream = response.bytes_stream();
+    let transfer = async {
+        // Dropping the sender at the end of the body lets the extractor see the end of the archive
+        let sender = sender;
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            hasher.update(&chunk);
+            downloaded += chunk.len() as u64;
+            http.events().emit(ServerEvent::DownloadProgress { downloaded, total_bytes: total_size });
+            
+            // The extractor only stops reading early when it failed, which is reported below
+            if sender.send(chunk).await.is_err() {
+                break;
+            }
+        }
+        Ok::<_, ServerError>(())
+    };
+    
+    let (transfer, extracted) = tokio::join!(transfer, extraction);
+    transfer?;
+    extracted?;
+    
+    if let Some(total_size) = total_size
+        && downloaded != total_size
+    {
+        return Err(ServerError::DownloadFailed(format!(
+            "Incomplete download: received {} of {} bytes", downloaded, total_size
+        )));
+    }
+    http.events().emit(ServerEvent::DownloadFinished { bytes: downloaded });
+    Ok(hex_encode(&hasher.finalize()))
+}
+
+/// A blocking `Read` over chunks received from a `tokio` channel
+struct ChannelReader<B> {
+    receiver: tokio::sync::mpsc::Receiver<B>,
+    chunk: Option<B>,
+    position: usize,
+}
+
+impl<B: AsRef<[u8]>> ChannelReader<B> {
+    fn new(receiver: tokio::sync::mpsc::Receiver<B>) -> Self {
+        Self { receiver, chunk: None, position: 0 }
+    }
 }
 
-/// Extracts a zip archive (Windows)
-#[cfg(target_os = "windows")]
-fn extract_zip(archive_path: &Path, target_dir: &Path) -> Result<(), ServerError> {
-    use zip::ZipArchive;
+impl<B: AsRef<[u8]>> Read for ChannelReader<B> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            if let Some(chunk) = &self.chunk {
+                let remaining = &chunk.as_ref()[self.position..];
+                if !remaining.is_empty() {
+                    let len = remaining.len().min(buf.len());
+                    buf[..len].copy_from_slice(&remaining[..len]);
+                    self.position += len;
+                    return Ok(len);
+                }
+            }
+            // The sender is dropped at the end of the body
+            match self.receiver.blocking_recv() {
+                Some(chunk) => {
+                    self.chunk = Some(chunk);
+                    self.position = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+    }
+}
+
+/// Validators of a partially downloaded archive, stored next to it so the download can be resumed
+#[derive(Serialize, Deserialize)]
+struct PartialDownload {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    total_size: Option<u64>,
+}
+
+impl PartialDownload {
+    /// The `If-Range` validator, preferring the strong `ETag` over `Last-Modified`
+    fn validator(&self) -> Option<&str> {
+        self.etag.as_deref()
+            .filter(|etag| !etag.starts_with("W/"))
+            .or(self.last_modified.as_deref())
+    }
+}
+
+/// Downloads `url` to `archive_path`, resuming a previously interrupted download when possible.
+///
+/// Data is streamed into `<archive>.part`, with the response's `ETag`, `Last-Modified` and
+/// `Content-Length` recorded in `<archive>.part.json`. If both exist from an earlier attempt,
+/// the download resumes with a `Range`/`If-Range` request; a server that ignores the range,
+/// a changed validator or an inconsistent `Content-Range` leads to a clean restart. The
+/// partial file is kept when the transfer is interrupted so that the next attempt can resume,
+/// and is renamed to `archive_path` once its size matches the expected length.
+///
+/// Returns the hex-encoded SHA-256 hash of the complete archive, including any resumed bytes.
+async fn download_archive(http: &HttpClient, url: &str, archive_path: &Path) -> Result<String, ServerError> {
+    let part_path = with_suffix(archive_path, ".part");
+    let meta_path = with_suffix(archive_path, ".part.json");
+    
+    // Look for a resumable partial download of the same URL
+    let partial_len = tokio::fs::metadata(&part_path).await.ok().map(|metadata| metadata.len());
+    let resume = tokio::fs::read_to_string(&meta_path).await.ok()
+        .and_then(|json| serde_json::from_str::<PartialDownload>(&json).ok())
+        .filter(|partial| partial.url == url && partial.validator().is_some())
+        .and_then(|partial| Some((partial_len?, partial)))
+        .filter(|(offset, partial)| *offset > 0 && partial.total_size.is_none_or(|total| *offset < total));
+    
+    let mut response = http.send(|client| {
+        let request = client.get(url);
+        match &resume {
+            Some((offset, partial)) => request
+                .header(RANGE, format!("bytes={}-", offset))
+                .header(IF_RANGE, partial.validator().unwrap_or_default()),
+            None => request,
+        }
+    }).await?;
+    
+    let resumed = match resume {
+        Some((offset, partial)) if response.status() == StatusCode::PARTIAL_CONTENT => {
+            let content_range = response.headers().get(CONTENT_RANGE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(parse_content_range);
+            match content_range {
+                Some((start, total)) if start == offset && total.is_none_or(|t| partial.total_size.is_none_or(|p| p == t)) => {
+                    Some((offset, partial.total_size.or(total)))
+                }
+                _ => {
+                    http.events().emit(ServerEvent::Warning(
+                        "Server returned an unexpected range, restarting download".to_string()
+                    ));
+                    response = http.send(|client| client.get(url)).await?;
+                    None
+                }
+            }
+        }
+        Some(_) if response.status() == StatusCode::RANGE_NOT_SATISFIABLE => {
+            response = http.send(|client| client.get(url)).await?;
+            None
+        }
+        _ => None,
+    };
     
-    let file = File::open(archive_path)?;
-    let mut archive = ZipArchive::new(file)
-        .map_err(|e| ServerError::ExtractionFailed(e.to_string()))?;
+    if !response.status().is_success() {
+        return Err(ServerError::DownloadFailed(
+            format!("Failed to download: {}", response.status())
+        ));
+    }
+    
+    // Fail before writing anything if the archive and the extracted server would not fit
+    if let (Some(remaining), Some(dir)) = (response.content_length(), archive_path.parent()) {
+        let archive_size = resumed.as_ref().map_or(0, |(offset, _)| *offset) + remaining;
+        check_free_space(dir, remaining.saturating_add(archive_size.saturating_mul(EXTRACTION_FACTOR)))?;
+    }
+    
+    let (mut file, mut hasher, mut downloaded, total_size) = match resumed {
+        Some((offset, total_size)) => {
+            let hasher = hash_file(&part_path).await?;
+            let file = tokio::fs::OpenOptions::new().append(true).open(&part_path).await?;
+            (file, hasher, offset, total_size)
+        }
+        None => {
+            let header = |name| response.headers().get(name)
+                .and_then(|value: &HeaderValue| value.to_str().ok())
+                .map(str::to_string);
+            let partial = PartialDownload {
+                url: url.to_string(),
+                etag: header(ETAG),
+                last_modified: header(LAST_MODIFIED),
+                total_size: response.content_length(),
+            };
+            let json = serde_json::to_string(&partial).map_err(|e| ServerError::Io(e.into()))?;
+            tokio::fs::write(&meta_path, json).await?;
+            (tokio::fs::File::create(&part_path).await?, Sha256::new(), 0, partial.total_size)
+        }
+    };
+    
+    http.events().emit(ServerEvent::DownloadStarted {
+        url: url.to_string(),
+        resumed_from: downloaded,
+        total_bytes: total_size,
+    });
     
-    fs::create_dir_all(target_dir)?;
-    archive.extract(target_dir)
-        .map_err(|e| ServerError::ExtractionFailed(e.to_string()))?;
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        file.write_all(&chunk).await?;
+        hasher.update(&chunk);
+        downloaded += chunk.len() as u64;
+        
+        http.events().emit(ServerEvent::DownloadProgress { downloaded, total_bytes: total_size });
+    }
+    file.flush().await?;
+    drop(file);
     
+    if let Some(total_size) = total_size.filter(|total| *total != downloaded) {
+        let _ = tokio::fs::remove_file(&part_path).await;
+        let _ = tokio::fs::remove_file(&meta_path).await;
+        return Err(ServerError::DownloadFailed(format!(
+            "Downloaded {} bytes but expected {}",
+            downloaded, total_size
+        )));
+    }
+    
+    tokio::fs::rename(&part_path, archive_path).await?;
+    let _ = tokio::fs::remove_file(&meta_path).await;
+    http.events().emit(ServerEvent::DownloadFinished { bytes: downloaded });
+    Ok(hex_encode(&hasher.finalize()))
+}
+
+/// Creates the server directory `dir`
+async fn create_server_dir(dir: &Path) -> Result<(), ServerError> {
+    tokio::fs::create_dir_all(dir).await
+        .map_err(|source| ServerError::NotWritable { path: dir.to_path_buf(), source })
+}
+
+/// Checks that files can be created in `dir` by writing and removing an empty probe file
+async fn check_writable(dir: &Path) -> Result<(), ServerError> {
+    let probe = dir.join(format!(".write-test-{}", std::process::id()));
+    match tokio::fs::write(&probe, b"").await {
+        Ok(()) => {
+            let _ = tokio::fs::remove_file(&probe).await;
+            Ok(())
+        }
+        Err(source) => Err(ServerError::NotWritable { path: dir.to_path_buf(), source }),
+    }
+}
+
+/// Checks that the file system holding `dir` has at least `required` bytes available
+fn check_free_space(dir: &Path, required: u64) -> Result<(), ServerError> {
+    let available = fs4::available_space(dir)?;
+    if available < required {
+        return Err(ServerError::InsufficientSpace { path: dir.to_path_buf(), required, available });
+    }
     Ok(())
 }
 
-#[cfg(not(target_os = "windows"))]
-fn extract_zip(_archive_path: &Path, _target_dir: &Path) -> Result<(), ServerError> {
-    Err(ServerError::ExtractionFailed(
-        "ZIP extraction not supported on this platform".to_string()
-    ))
-}
\ No newline at end of file
+/// Encodes bytes as a lowercase hex string
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Parses a `Content-Range: bytes <start>-<end>/<total>` header into its start offset and total size
+fn parse_content_range(value: &str) -> Option<(u64, Option<u64>)> {
+    let (range, total) = value.strip_prefix("bytes ")?.split_once('/')?;
+    let start = range.split_once('-')?.0.trim().parse().ok()?;
+    Some((start, total.trim().parse().ok()))
+}
+
+/// Appends `suffix` to the file name of `path`
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Extracts the downloaded archive into `staging_dir`, then moves the server to `target_dir`
+async fn extract_archive(
+    archive_path: &Path,
+    staging_dir: &Path,
+    target_dir: &Path,
+    platform: Platform,
+    events: &EventSink,
+) -> Result<(), ServerError> {
+    let (archive_path, target_dir) = (archive_path.to_path_buf(), target_dir.to_path_buf());
+    run_extraction(staging_dir, events, move |staging_dir, extraction| {
+        extract::unpack_archive(&archive_path, staging_dir, platform.uses_zip(), extraction)?;
+        // Once the future is dropped, the install lock is no longer held
+        extraction.check_cancelled()?;
+        extract::install_unpacked(staging_dir, &target_dir)
+    }).await
+}
+
+/// Runs `extract` on a blocking thread, reporting its progress per entry to `events`.
+///
+/// Dropping the returned future cancels the extraction: `extract` stops before its next entry
+/// and `staging_dir` is removed once it has.
+async fn run_extraction<T: Send + 'static>(
+    staging_dir: &Path,
+    events: &EventSink,
+    extract: impl FnOnce(&Path, &Extraction) -> Result<T, ServerError> + Send + 'static,
+) -> Result<T, ServerError> {
+    let extraction = Extraction::new(events);
+    let _cancel = extraction.cancel_on_drop();
+    let staging_dir = staging_dir.to_path_buf();
+    blocking(move || {
+        let result = extract(&staging_dir, &extraction);
+        // The staging directory may have been removed while the last entry was written
+        if extraction.is_cancelled() {
+            let _ = fs::remove_dir_all(&staging_dir);
+        }
+        result
+    }).await
+}
+
+/// Hashes the file at `path` with SHA-256 on a blocking thread
+async fn hash_file(path: &Path) -> Result<Sha256, ServerError> {
+    let path = path.to_path_buf();
+    blocking(move || {
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut File::open(&path)?, &mut hasher)?;
+        Ok(hasher)
+    }).await
+}
+
+/// Runs the file system operation `f` on a blocking thread, off the async runtime
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, ServerError> + Send + 'static,
+) -> Result<T, ServerError> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| ServerError::Io(std::io::Error::other(e)))?
+}
+
+/// A staging directory next to an install, unique to one install attempt.
+///
+/// It is removed when dropped, whether or not the install succeeded. Within a `tokio` runtime,
+/// the removal runs on a blocking thread and may finish after the drop.
+struct StagingDir(PathBuf);
+
+impl StagingDir {
+    /// Picks a staging directory for installing `target_dir` that no other attempt uses
+    fn new(target_dir: &Path) -> Self {
+        static ATTEMPTS: AtomicU64 = AtomicU64::new(0);
+        let attempt = ATTEMPTS.fetch_add(1, Ordering::Relaxed);
+        Self(with_suffix(target_dir, &format!(".{}-{}.tmp", std::process::id(), attempt)))
+    }
+    
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for StagingDir {
+    fn drop(&mut self) {
+        let path = std::mem::take(&mut self.0);
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn_blocking(move || fs::remove_dir_all(path));
+            }
+            Err(_) => {
+                let _ = fs::remove_dir_all(path);
+            }
+        }
+    }
+}
+
+/// Takes the advisory install lock at `lock_path`, waiting while another process holds it.
+///
+/// The lock is released when the returned file is closed.
+async fn lock_install(lock_path: &Path, events: &EventSink) -> Result<File, ServerError> {
+    let file = OpenOptions::new().create(true).truncate(false).write(true).open(lock_path)?;
+    let mut waiting = false;
+    loop {
+        match file.try_lock() {
+            Ok(()) => return Ok(file),
+            Err(TryLockError::WouldBlock) => {
+                if !waiting {
+                    events.emit(ServerEvent::WaitingForLock { path: lock_path.to_path_buf() });
+                    waiting = true;
+                }
+                tokio::time::sleep(Duration::from_millis(200)).await;
+            }
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{serve, Reply};
+    use std::sync::{Arc, Mutex};
+
+    /// The archive served by the test server
+    const BODY: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+    fn full_reply() -> Reply {
+        Reply::new("200 OK", &["ETag: \"v1\""], BODY)
+    }
+
+    fn partial_reply(start: usize, total: usize) -> Reply {
+        let content_range = format!("Content-Range: bytes {}-{}/{}", start, BODY.len() - 1, total);
+        Reply::new("206 Partial Content", &[&content_range], &BODY[start..])
+    }
+
+    fn not_satisfiable_reply() -> Reply {
+        Reply::new("416 Range Not Satisfiable", &[], b"")
+    }
+
+    /// Serves `replies` in order, returning the archive URL and the received requests
+    fn serve_archive(replies: Vec<Reply>) -> (String, Arc<Mutex<Vec<String>>>) {
+        let (base, requests) = serve(replies);
+        (format!("{}/server.tar.gz", base), requests)
+    }
+
+    /// Leaves the first `len` bytes of `BODY` as an interrupted download of `url`
+    fn write_partial(archive_path: &Path, url: &str, len: usize) {
+        fs::write(with_suffix(archive_path, ".part"), &BODY[..len]).unwrap();
+        let partial = PartialDownload {
+            url: url.to_string(),
+            etag: Some("\"v1\"".to_string()),
+            last_modified: None,
+            total_size: Some(BODY.len() as u64),
+        };
+        fs::write(with_suffix(archive_path, ".part.json"), serde_json::to_string(&partial).unwrap()).unwrap();
+    }
+
+    /// Downloads `url` to `archive_path` without retries
+    async fn download(url: &str, archive_path: &Path) -> Result<String, ServerError> {
+        let config = ServerConfig {
+            retry: crate::RetryPolicy { max_attempts: 1, ..Default::default() },
+            ..Default::default()
+        };
+        let http = HttpClient::new(&config, &EventSink::default()).unwrap();
+        download_archive(&http, url, archive_path).await
+    }
+
+    /// Checks that `archive_path` holds the complete archive and no partial download is left
+    fn assert_complete(archive_path: &Path, hash: &str) {
+        assert_eq!(fs::read(archive_path).unwrap(), BODY);
+        assert_eq!(hash, hex_encode(&Sha256::digest(BODY)));
+        assert!(!with_suffix(archive_path, ".part").exists());
+        assert!(!with_suffix(archive_path, ".part.json").exists());
+    }
+
+    #[test]
+    fn parses_content_range() {
+        assert_eq!(parse_content_range("bytes 100-199/200"), Some((100, Some(200))));
+        assert_eq!(parse_content_range("bytes 0-0/*"), Some((0, None)));
+        assert_eq!(parse_content_range("bytes */200"), None);
+        assert_eq!(parse_content_range("items 0-1/2"), None);
+        assert_eq!(parse_content_range("bytes x-1/2"), None);
+    }
+
+    #[test]
+    fn prefers_strong_etag_as_validator() {
+        let mut partial = PartialDownload {
+            url: String::new(),
+            etag: Some("\"v1\"".to_string()),
+            last_modified: Some("Wed, 21 Oct 2015 07:28:00 GMT".to_string()),
+            total_size: None,
+        };
+        assert_eq!(partial.validator(), Some("\"v1\""));
+        partial.etag = Some("W/\"v1\"".to_string());
+        assert_eq!(partial.validator(), Some("Wed, 21 Oct 2015 07:28:00 GMT"));
+        partial.last_modified = None;
+        assert_eq!(partial.validator(), None);
+    }
+
+    #[tokio::test]
+    async fn downloads_without_partial_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive_path = dir.path().join("server.tar.gz");
+        let (url, requests) = serve_archive(vec![full_reply()]);
+
+        let hash = download(&url, &archive_path).await.unwrap();
+        assert_complete(&archive_path, &hash);
+        assert!(!requests.lock().unwrap()[0].contains("range:"));
+    }
+
+    #[tokio::test]
+    async fn resumes_partial_download() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive_path = dir.path().join("server.tar.gz");
+        let (url, requests) = serve_archive(vec![partial_reply(10, BODY.len())]);
+        write_partial(&archive_path, &url, 10);
+
+        let hash = download(&url, &archive_path).await.unwrap();
+        assert_complete(&archive_path, &hash);
+        let requests = requests.lock().unwrap();
+        assert!(requests[0].contains("range: bytes=10-"));
+        assert!(requests[0].contains("if-range: \"v1\""));
+    }
+
+    #[tokio::test]
+    async fn restarts_when_range_is_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive_path = dir.path().join("server.tar.gz");
+        let (url, requests) = serve_archive(vec![full_reply()]);
+        write_partial(&archive_path, &url, 10);
+
+        let hash = download(&url, &archive_path).await.unwrap();
+        assert_complete(&archive_path, &hash);
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn restarts_when_range_is_not_satisfiable() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive_path = dir.path().join("server.tar.gz");
+        let (url, requests) = serve_archive(vec![not_satisfiable_reply(), full_reply()]);
+        write_partial(&archive_path, &url, 10);
+
+        let hash = download(&url, &archive_path).await.unwrap();
+        assert_complete(&archive_path, &hash);
+        let requests = requests.lock().unwrap();
+        assert!(requests[0].contains("range: bytes=10-"));
+        assert!(!requests[1].contains("range:"));
+    }
+
+    #[tokio::test]
+    async fn restarts_on_mismatched_content_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive_path = dir.path().join("server.tar.gz");
+        let (url, requests) = serve_archive(vec![partial_reply(12, BODY.len()), full_reply()]);
+        write_partial(&archive_path, &url, 10);
+
+        let hash = download(&url, &archive_path).await.unwrap();
+        assert_complete(&archive_path, &hash);
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(!requests[1].contains("range:"));
+    }
+
+    #[tokio::test]
+    async fn restarts_when_total_size_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive_path = dir.path().join("server.tar.gz");
+        let (url, _) = serve_archive(vec![partial_reply(10, BODY.len() + 1), full_reply()]);
+        write_partial(&archive_path, &url, 10);
+
+        let hash = download(&url, &archive_path).await.unwrap();
+        assert_complete(&archive_path, &hash);
+    }
+
+    /// Describes the stable server of `commit` for `platform`, downloaded from `download_url`
+    fn server_info(commit: &str, platform: Platform, download_url: &str) -> ServerInfo {
+        ServerInfo {
+            monaco_api_version: "v16.1.1".to_string(),
+            vscode_commit: commit.to_string(),
+            vscode_version: None,
+            vscode_ref: None,
+            platform,
+            quality: crate::Quality::Stable,
+            download_url: download_url.to_string(),
+            source: ResolutionSource::Resolver,
+            resolved_at: 0,
+            downloaded_from: None,
+        }
+    }
+
+    /// Builds a minimal server archive
+    fn server_archive() -> Vec<u8> {
+        let encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        for (path, contents) in [("vscode-server/bin/code-server", "#!/bin/sh\n"), ("vscode-server/product.json", "{}")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o755);
+            header.set_cksum();
+            builder.append_data(&mut header, path, contents.as_bytes()).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    /// Installs the host's server into `server_dir` from `mirror`, then `download_url`, without
+    /// retries. Returns the result and the emitted events; a successful install must record the
+    /// URL it was downloaded from.
+    async fn install_from_mirror(
+        server_dir: &Path,
+        mirror: &str,
+        download_url: &str,
+        expected_sha256: &str,
+    ) -> (Result<Option<String>, ServerError>, Vec<ServerEvent>) {
+        let config = ServerConfig {
+            server_dir: server_dir.to_path_buf(),
+            mirrors: vec![mirror.to_string()],
+            expected_sha256: Some(expected_sha256.to_string()),
+            retry: crate::RetryPolicy { max_attempts: 1, ..Default::default() },
+            ..Default::default()
+        };
+        let info = server_info("863d2581ecda11849ec1d1fdc1f7e6d21c0e1b76", Platform::current().unwrap(), download_url);
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = EventSink::new({
+            let events = events.clone();
+            move |event| events.lock().unwrap().push(event)
+        });
+        let result = download_server_with_events(&info, &config, &sink).await;
+        if let Ok(downloaded_from) = &result {
+            let record = install::read_marker(&server_dir.join(info.install_dir_name())).unwrap();
+            assert_eq!(&record.info.downloaded_from, downloaded_from);
+        }
+        let events = events.lock().unwrap().clone();
+        (result, events)
+    }
+
+    /// Returns the URLs of the mirrors reported as failed in `events`
+    fn failed_mirrors(events: &[ServerEvent]) -> Vec<&str> {
+        events.iter()
+            .filter_map(|event| match event {
+                ServerEvent::MirrorFailed { url, .. } => Some(url.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn falls_through_to_next_mirror_on_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = server_archive();
+        let (failing, _) = serve_archive(vec![Reply::new("500 Internal Server Error", &[], b"")]);
+        let (working, _) = serve_archive(vec![Reply::new("200 OK", &[], &archive)]);
+
+        let sha256 = hex_encode(&Sha256::digest(&archive));
+        let (result, events) = install_from_mirror(dir.path(), &failing, &working, &sha256).await;
+        assert_eq!(result.unwrap().as_deref(), Some(working.as_str()));
+        assert_eq!(failed_mirrors(&events), vec![failing.as_str()]);
+    }
+
+    #[tokio::test]
+    async fn falls_through_to_next_mirror_on_checksum_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = server_archive();
+        let (tampered, _) = serve_archive(vec![Reply::new("200 OK", &[], b"not the server archive")]);
+        let (working, _) = serve_archive(vec![Reply::new("200 OK", &[], &archive)]);
+
+        let sha256 = hex_encode(&Sha256::digest(&archive));
+        let (result, events) = install_from_mirror(dir.path(), &tampered, &working, &sha256).await;
+        assert_eq!(result.unwrap().as_deref(), Some(working.as_str()));
+        assert_eq!(failed_mirrors(&events), vec![tampered.as_str()]);
+        let reason = events.iter().find_map(|event| match event {
+            ServerEvent::MirrorFailed { reason, .. } => Some(reason.clone()),
+            _ => None,
+        });
+        assert!(reason.unwrap().starts_with("Checksum mismatch"));
+    }
+
+    #[tokio::test]
+    async fn removes_archive_on_checksum_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive_path = dir.path().join("server.tar.gz");
+        let (url, _) = serve_archive(vec![full_reply()]);
+        let http = HttpClient::new(&ServerConfig::default(), &EventSink::default()).unwrap();
+
+        let expected = "0".repeat(64);
+        let result = fetch_archive(&http, &url, &archive_path, Some(&expected)).await;
+        let Err(ServerError::ChecksumMismatch { expected: reported, actual }) = result else {
+            panic!("expected a checksum mismatch, got {:?}", result);
+        };
+        assert_eq!(reported, expected);
+        assert_eq!(actual, hex_encode(&Sha256::digest(BODY)));
+        assert!(!archive_path.exists());
+        assert!(!with_suffix(&archive_path, ".part").exists());
+    }
+
+    /// Looks up the stable linux-x64 server of `commit` in a checksum file holding `contents`
+    fn lookup_checksum(contents: &str, commit: &str) -> Result<Option<String>, ServerError> {
+        let dir = tempfile::tempdir().unwrap();
+        let checksum_file = dir.path().join("checksums.txt");
+        fs::write(&checksum_file, contents).unwrap();
+        let config = ServerConfig { checksum_file: Some(checksum_file), ..Default::default() };
+        let info = server_info(commit, Platform::LinuxX64, "");
+        checksum_file_entry(&config, &info)
+    }
+
+    #[test]
+    fn parses_checksum_file_entries() {
+        let contents = "\
+# Server archives for monaco-vscode-api v16.1.1
+1111111111111111111111111111111111111111111111111111111111111111  aaaa/server-linux-x64/stable
+
+2222222222222222222222222222222222222222222222222222222222222222 *bbbb/server-linux-x64/stable
+# 3333333333333333333333333333333333333333333333333333333333333333  cccc/server-linux-x64/stable
+";
+        assert_eq!(lookup_checksum(contents, "aaaa").unwrap(), Some("1".repeat(64)));
+        assert_eq!(lookup_checksum(contents, "bbbb").unwrap(), Some("2".repeat(64)));
+        assert!(matches!(lookup_checksum(contents, "cccc"), Err(ServerError::DownloadFailed(_))));
+        assert!(matches!(lookup_checksum(contents, "dddd"), Err(ServerError::DownloadFailed(_))));
+    }
+}
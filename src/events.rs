@@ -0,0 +1,173 @@
+// events.rs - Events reported while preparing the VSCode server
+
+use crate::{Platform, Quality, ServerInfo};
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+
+/// An event reported while resolving, downloading or installing the VSCode server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ServerEvent {
+    /// Resolution of the server version has started.
+    ResolveStarted {
+        /// The platform the server is resolved for.
+        platform: Platform,
+        /// The requested release quality.
+        quality: Quality,
+    },
+    /// The monaco-vscode-api version was read from a frontend project's installed package, lockfile
+    /// or `package.json`.
+    FrontendVersionDetected {
+        /// The version or version range found.
+        version: String,
+        /// The file it was read from.
+        source: PathBuf,
+    },
+    /// The server version was resolved. `ServerInfo::source` tells whether it came from a cache.
+    Resolved {
+        /// The resolved server.
+        info: ServerInfo,
+    },
+    /// Leftovers of an interrupted install were removed before installing again: an install
+    /// directory without completion marker, staging directories or unextracted archives.
+    IncompleteInstallRemoved {
+        /// The removed files and directories.
+        paths: Vec<PathBuf>,
+    },
+    /// A server installed by an earlier version of this crate, without install marker or under
+    /// its former directory name, was validated and adopted instead of downloading it again.
+    LegacyInstallAdopted {
+        /// The directory the server was found in.
+        from: PathBuf,
+        /// The install directory the server now lives in.
+        path: PathBuf,
+    },
+    /// Another process is installing the same server; the install waits for it to finish.
+    WaitingForLock {
+        /// The lock file held by the other process.
+        path: PathBuf,
+    },
+    /// The server archive download has started.
+    DownloadStarted {
+        /// The URL of the archive.
+        url: String,
+        /// The number of bytes already present from an interrupted download.
+        resumed_from: u64,
+        /// The total size of the archive, if known.
+        total_bytes: Option<u64>,
+    },
+    /// Downloading from a mirror failed; the next mirror is tried.
+    MirrorFailed {
+        /// The URL of the archive on the failed mirror.
+        url: String,
+        /// Why the download failed.
+        reason: String,
+    },
+    /// A chunk of the server archive was received.
+    DownloadProgress {
+        /// The number of bytes downloaded so far, including resumed bytes.
+        downloaded: u64,
+        /// The total size of the archive, if known.
+        total_bytes: Option<u64>,
+    },
+    /// The server archive was downloaded completely.
+    DownloadFinished {
+        /// The size of the archive.
+        bytes: u64,
+    },
+    /// The server archive matched its expected SHA-256 checksum.
+    ChecksumVerified {
+        /// The verified checksum.
+        sha256: String,
+    },
+    /// Extraction of the server archive has started.
+    ///
+    /// With `ServerConfig::streaming_extraction`, this is reported before the download starts.
+    ExtractionStarted {
+        /// The archive being extracted. It is not written to disk when extracting while streaming.
+        archive: PathBuf,
+    },
+    /// An entry of the server archive was extracted.
+    ExtractionProgress {
+        /// The number of entries extracted so far.
+        entries: u64,
+        /// The total number of entries, if known. Only zip archives list their entries upfront.
+        total_entries: Option<u64>,
+        /// The uncompressed size of the files extracted so far.
+        bytes: u64,
+    },
+    /// The server archive was extracted.
+    ExtractionFinished {
+        /// The directory the server was extracted to.
+        path: PathBuf,
+    },
+    /// The server is installed and ready to start.
+    Installed {
+        /// The install directory of the server.
+        path: PathBuf,
+    },
+    /// A request failed with a retryable error and will be retried after `delay`.
+    RetryScheduled {
+        /// The URL of the failed request.
+        url: String,
+        /// The attempt that failed, starting at 1.
+        attempt: u32,
+        /// The maximum number of attempts of the retry policy.
+        max_attempts: u32,
+        /// How long until the next attempt.
+        delay: Duration,
+        /// Why the attempt failed.
+        reason: String,
+    },
+    /// A non-fatal problem, such as a missing checksum or a failed cache write.
+    Warning(String),
+}
+
+/// Receives `ServerEvent`s, either through a callback or a channel.
+///
+/// The default sink discards all events.
+#[derive(Clone, Default)]
+pub struct EventSink(Option<Arc<dyn Fn(ServerEvent) + Send + Sync>>);
+
+impl EventSink {
+    /// Creates a sink invoking `callback` for every event.
+    pub fn new(callback: impl Fn(ServerEvent) + Send + Sync + 'static) -> Self {
+        Self(Some(Arc::new(callback)))
+    }
+
+    /// Creates a sink forwarding every event to an unbounded `tokio` channel.
+    ///
+    /// Events are dropped once the receiver is closed.
+    pub fn channel(sender: mpsc::UnboundedSender<ServerEvent>) -> Self {
+        Self::new(move |event| {
+            let _ = sender.send(event);
+        })
+    }
+
+    /// Creates a sink publishing the latest event to a `tokio` watch channel.
+    ///
+    /// Useful to display progress without queueing every intermediate event.
+    pub fn watch(sender: watch::Sender<Option<ServerEvent>>) -> Self {
+        Self::new(move |event| {
+            sender.send_replace(Some(event));
+        })
+    }
+
+    /// Delivers `event` to the sink.
+    pub(crate) fn emit(&self, event: ServerEvent) {
+        if let Some(callback) = &self.0 {
+            callback(event);
+        }
+    }
+}
+
+impl fmt::Debug for EventSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("EventSink")
+            .field(&if self.0.is_some() { "callback" } else { "none" })
+            .finish()
+    }
+}
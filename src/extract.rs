@@ -0,0 +1,566 @@
+// extract.rs - Safe extraction of server archives
+
+use crate::events::{EventSink, ServerEvent};
+use crate::install;
+use crate::ServerError;
+use flate2::read::GzDecoder;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tar::{Archive, EntryType};
+
+/// The maximum number of entries an archive may contain
+const MAX_ENTRIES: u64 = 100_000;
+
+/// The maximum total size of the files in an archive once unpacked (4 GiB)
+const MAX_UNPACKED_BYTES: u64 = 4 * 1024 * 1024 * 1024;
+
+/// A running extraction: reports its progress per entry and stops once cancelled.
+///
+/// Extraction runs on a blocking thread, so it cannot be cancelled by dropping a future; the
+/// async side holds a `CancelOnDrop` guard instead, and the extractor checks it before every entry.
+#[derive(Clone)]
+pub(crate) struct Extraction {
+    events: EventSink,
+    cancelled: Arc<AtomicBool>,
+    max_entries: u64,
+    max_unpacked_bytes: u64,
+}
+
+impl Extraction {
+    /// Creates an extraction reporting its progress to `events`
+    pub(crate) fn new(events: &EventSink) -> Self {
+        Self {
+            events: events.clone(),
+            cancelled: Arc::new(AtomicBool::new(false)),
+            max_entries: MAX_ENTRIES,
+            max_unpacked_bytes: MAX_UNPACKED_BYTES,
+        }
+    }
+    
+    /// Lowers the entry count and unpacked size limits
+    #[cfg(test)]
+    fn with_limits(mut self, max_entries: u64, max_unpacked_bytes: u64) -> Self {
+        self.max_entries = max_entries;
+        self.max_unpacked_bytes = max_unpacked_bytes;
+        self
+    }
+    
+    /// Fails with an error if an archive has more than the allowed number of entries
+    fn check_entries(&self, entries: u64) -> Result<(), ServerError> {
+        if entries > self.max_entries {
+            return Err(ServerError::ExtractionFailed(format!(
+                "Archive has more than {} entries", self.max_entries
+            )));
+        }
+        Ok(())
+    }
+    
+    /// Fails with an error if an archive unpacks to more than the allowed number of bytes
+    fn check_unpacked_bytes(&self, bytes: u64) -> Result<(), ServerError> {
+        if bytes > self.max_unpacked_bytes {
+            return Err(ServerError::ExtractionFailed(format!(
+                "Archive unpacks to more than {} bytes", self.max_unpacked_bytes
+            )));
+        }
+        Ok(())
+    }
+    
+    /// Returns a guard that cancels the extraction when dropped
+    pub(crate) fn cancel_on_drop(&self) -> CancelOnDrop {
+        CancelOnDrop(self.cancelled.clone())
+    }
+    
+    /// Checks whether the extraction was cancelled
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+    
+    /// Fails with an error if the extraction was cancelled
+    pub(crate) fn check_cancelled(&self) -> Result<(), ServerError> {
+        if self.is_cancelled() {
+            return Err(ServerError::ExtractionFailed("Extraction was cancelled".to_string()));
+        }
+        Ok(())
+    }
+    
+    /// Reports that `entries` entries holding `bytes` bytes have been extracted
+    fn progress(&self, entries: u64, total_entries: Option<u64>, bytes: u64) {
+        self.events.emit(ServerEvent::ExtractionProgress { entries, total_entries, bytes });
+    }
+}
+
+/// Cancels an `Extraction` when dropped
+pub(crate) struct CancelOnDrop(Arc<AtomicBool>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Unpacks a zip or tar.gz archive into `staging_dir`
+pub(crate) fn unpack_archive(
+    archive_path: &Path,
+    staging_dir: &Path,
+    zip: bool,
+    extraction: &Extraction,
+) -> Result<(), ServerError> {
+    fs::create_dir_all(staging_dir)?;
+    if zip {
+        unpack_zip(archive_path, staging_dir, extraction)
+    } else {
+        unpack_tar_gz(File::open(archive_path)?, staging_dir, extraction)
+    }
+}
+
+/// Unpacks a tar.gz stream into `dir` entry by entry, then reads `reader` to its end.
+///
+/// Every entry is validated before it is written: absolute paths, `..` components, links
+/// pointing outside `dir` and special files (devices, FIFOs) are rejected, and the archive
+/// may hold at most `MAX_ENTRIES` entries and `MAX_UNPACKED_BYTES` of file data. As paths are
+/// checked lexically, paths and link targets going through a symlink unpacked earlier are
+/// rejected as well, along with hard links to symlinks: once the symlink is resolved on disk,
+/// either could lead outside `dir`. Permission bits, including the executable bits of
+/// `bin/code-server` and `node`, are preserved, while setuid, setgid and sticky bits are dropped.
+///
+/// Progress is reported after every entry, and the extraction stops before the next entry once
+/// `extraction` is cancelled.
+pub(crate) fn unpack_tar_gz(reader: impl Read, dir: &Path, extraction: &Extraction) -> Result<(), ServerError> {
+    let mut archive = Archive::new(GzDecoder::new(reader));
+    let mut entry_count = 0;
+    let mut unpacked_bytes: u64 = 0;
+    let mut symlinks = HashSet::new();
+
+    for entry in archive.entries().map_err(extraction_error)? {
+        extraction.check_cancelled()?;
+        let mut entry = entry.map_err(extraction_error)?;
+        let entry_type = entry.header().entry_type();
+        if entry_type.is_pax_global_extensions() {
+            continue;
+        }
+
+        entry_count += 1;
+        extraction.check_entries(entry_count)?;
+
+        let path = entry.path().map_err(extraction_error)?.into_owned();
+        let normalized = normalize(&path)?;
+        if traverses_symlink(&path, &symlinks) {
+            return Err(ServerError::ExtractionFailed(format!(
+                "Path in archive goes through a symlink: {}", path.display()
+            )));
+        }
+        let parent = normalized.parent().map(Path::to_path_buf).unwrap_or_default();
+        match entry_type {
+            EntryType::Regular | EntryType::Continuous | EntryType::Directory => {}
+            EntryType::Symlink => {
+                // Symlink targets are relative to the link's directory
+                let target = link_target(&entry, &path)?;
+                let resolved = parent.join(&target);
+                if normalize(&resolved).is_err() || traverses_symlink(&resolved, &symlinks) {
+                    return Err(unsafe_link(&path, &target));
+                }
+                symlinks.insert(normalized);
+            }
+            EntryType::Link => {
+                // Hard link targets are relative to the archive root. A hard link to a symlink
+                // would be a copy of the symlink, whose target is relative to another directory
+                let target = link_target(&entry, &path)?;
+                let linked = normalize(&target).map_err(|_| unsafe_link(&path, &target))?;
+                if traverses_symlink(&target, &symlinks) || symlinks.contains(&linked) {
+                    return Err(unsafe_link(&path, &target));
+                }
+            }
+            other => {
+                return Err(ServerError::ExtractionFailed(format!(
+                    "Unsupported entry type {:?} for {}", other, path.display()
+                )));
+            }
+        }
+
+        unpacked_bytes = unpacked_bytes.saturating_add(entry.size());
+        extraction.check_unpacked_bytes(unpacked_bytes)?;
+
+        if !entry.unpack_in(dir).map_err(extraction_error)? {
+            return Err(ServerError::ExtractionFailed(format!(
+                "Unsafe path in archive: {}", path.display()
+            )));
+        }
+        extraction.progress(entry_count, None, unpacked_bytes);
+    }
+
+    // Consume any trailing data so that a streamed archive is hashed completely
+    std::io::copy(&mut archive.into_inner().into_inner(), &mut std::io::sink())?;
+    Ok(())
+}
+
+/// Unpacks a zip archive into `dir` entry by entry.
+///
+/// Entries whose paths would escape `dir` are rejected, and the same entry count and size limits
+/// as for tar archives apply. As the sizes declared in a zip archive may be wrong, the size limit
+/// is enforced on the bytes actually unpacked. Progress reporting and cancellation work as in
+/// `unpack_tar_gz`.
+fn unpack_zip(archive_path: &Path, dir: &Path, extraction: &Extraction) -> Result<(), ServerError> {
+    use zip::ZipArchive;
+
+    let file = File::open(archive_path)?;
+    let mut archive = ZipArchive::new(file)
+        .map_err(|e| ServerError::ExtractionFailed(e.to_string()))?;
+
+    extraction.check_entries(archive.len() as u64)?;
+    // Reject archives whose headers admit to being too large before unpacking anything
+    let mut declared_bytes: u64 = 0;
+    for index in 0..archive.len() {
+        let file = archive.by_index(index)
+            .map_err(|e| ServerError::ExtractionFailed(e.to_string()))?;
+        declared_bytes = declared_bytes.saturating_add(file.size());
+    }
+    extraction.check_unpacked_bytes(declared_bytes)?;
+
+    let total_entries = archive.len() as u64;
+    let mut unpacked_bytes: u64 = 0;
+    for index in 0..archive.len() {
+        extraction.check_cancelled()?;
+        let mut file = archive.by_index(index)
+            .map_err(|e| ServerError::ExtractionFailed(e.to_string()))?;
+        let Some(relative_path) = file.enclosed_name().map(Path::to_path_buf) else {
+            return Err(ServerError::ExtractionFailed(format!(
+                "Unsafe path in archive: {}", file.name()
+            )));
+        };
+
+        let path = dir.join(relative_path);
+        if file.is_dir() {
+            fs::create_dir_all(&path)?;
+        } else {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            // The declared sizes may lie, so count the bytes actually written against the budget
+            let remaining = extraction.max_unpacked_bytes.saturating_sub(unpacked_bytes);
+            let written = std::io::copy(&mut (&mut file).take(remaining.saturating_add(1)), &mut File::create(&path)?)?;
+            unpacked_bytes = unpacked_bytes.saturating_add(written);
+            extraction.check_unpacked_bytes(unpacked_bytes)?;
+        }
+        #[cfg(unix)]
+        if let Some(mode) = file.unix_mode() {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode & 0o777))?;
+        }
+
+        extraction.progress(index as u64 + 1, Some(total_entries), unpacked_bytes);
+    }
+    Ok(())
+}
+
+/// Moves the single top-level directory unpacked into `staging_dir` to `target_dir`.
+///
+/// The rename is atomic, so `target_dir` never exists in a partially extracted state.
+pub(crate) fn install_unpacked(staging_dir: &Path, target_dir: &Path) -> Result<(), ServerError> {
+    fs::rename(unpacked_root(staging_dir)?, target_dir)?;
+    Ok(())
+}
+
+/// Finds the server directory among the contents unpacked into `staging_dir`.
+///
+/// Archives hold a single top-level directory (e.g. `vscode-server-linux-x64`) containing the
+/// server, which must include its `bin/code-server` launcher.
+pub(crate) fn unpacked_root(staging_dir: &Path) -> Result<PathBuf, ServerError> {
+    // Find the vscode directory
+    let entries = fs::read_dir(staging_dir)?;
+    let mut found_dirs = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if entry.path().is_dir() {
+            found_dirs.push(entry.path());
+        }
+    }
+
+    if found_dirs.len() != 1 {
+        return Err(ServerError::ExtractionFailed(
+            format!("Expected exactly one directory in archive, found {}", found_dirs.len())
+        ));
+    }
+
+    let root = found_dirs.remove(0);
+    if !install::has_launcher(&root) {
+        return Err(ServerError::ExtractionFailed(
+            "Archive does not contain a VSCode server (bin/code-server is missing)".to_string()
+        ));
+    }
+    Ok(root)
+}
+
+/// Normalizes the relative `path` lexically, failing if it is absolute or leaves the directory
+/// it is relative to.
+fn normalize(path: &Path) -> Result<PathBuf, ServerError> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => normalized.push(name),
+            Component::CurDir => {}
+            Component::ParentDir if normalized.pop() => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                return Err(ServerError::ExtractionFailed(format!(
+                    "Unsafe path in archive: {}", path.display()
+                )));
+            }
+        }
+    }
+    Ok(normalized)
+}
+
+/// Checks whether resolving the relative `path` component by component would go through one of
+/// the already unpacked `symlinks`, so that its lexical form does not tell where it leads.
+fn traverses_symlink(path: &Path, symlinks: &HashSet<PathBuf>) -> bool {
+    let mut current = PathBuf::new();
+    for component in path.components() {
+        if symlinks.contains(&current) {
+            return true;
+        }
+        match component {
+            Component::Normal(name) => current.push(name),
+            Component::ParentDir => {
+                current.pop();
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Reads the target of the link entry at `path`
+fn link_target<R: Read>(entry: &tar::Entry<'_, R>, path: &Path) -> Result<PathBuf, ServerError> {
+    entry.link_name()
+        .map_err(extraction_error)?
+        .map(|target| target.into_owned())
+        .ok_or_else(|| ServerError::ExtractionFailed(format!("Link without target in archive: {}", path.display())))
+}
+
+fn unsafe_link(path: &Path, target: &Path) -> ServerError {
+    ServerError::ExtractionFailed(format!(
+        "Link in archive points outside the server directory: {} -> {}",
+        path.display(),
+        target.display()
+    ))
+}
+
+fn extraction_error(e: std::io::Error) -> ServerError {
+    ServerError::ExtractionFailed(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use tar::Header;
+    use tempfile::TempDir;
+
+    /// An entry of a test archive; names are written verbatim, bypassing `tar::Builder`'s checks
+    enum Entry {
+        File(&'static str, &'static [u8]),
+        Dir(&'static str),
+        Symlink(&'static str, &'static str),
+        HardLink(&'static str, &'static str),
+        Device(&'static str),
+    }
+
+    /// Builds a tar.gz archive from `entries`
+    fn archive(entries: &[Entry]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::fast()));
+        for entry in entries {
+            let (name, entry_type, link, data): (&str, EntryType, &str, &[u8]) = match *entry {
+                Entry::File(name, data) => (name, EntryType::Regular, "", data),
+                Entry::Dir(name) => (name, EntryType::Directory, "", b""),
+                Entry::Symlink(name, target) => (name, EntryType::Symlink, target, b""),
+                Entry::HardLink(name, target) => (name, EntryType::Link, target, b""),
+                Entry::Device(name) => (name, EntryType::Char, "", b""),
+            };
+            let mut header = Header::new_old();
+            header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.as_old_mut().linkname[..link.len()].copy_from_slice(link.as_bytes());
+            header.set_entry_type(entry_type);
+            header.set_mode(if entry_type == EntryType::Directory { 0o755 } else { 0o644 });
+            header.set_size(data.len() as u64);
+            header.set_cksum();
+            builder.append(&header, data).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    /// Unpacks `entries` into a fresh temporary directory
+    fn unpack(entries: &[Entry], extraction: &Extraction) -> Result<TempDir, ServerError> {
+        let dir = tempfile::tempdir().unwrap();
+        unpack_tar_gz(archive(entries).as_slice(), dir.path(), extraction).map(|()| dir)
+    }
+
+    /// Builds a zip archive of `(name, contents, unix mode)` files; names ending in `/` are directories
+    fn zip_archive(files: &[(&str, &[u8], u32)]) -> Vec<u8> {
+        use std::io::Write;
+        use zip::write::FileOptions;
+
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for (name, contents, mode) in files {
+            let options = FileOptions::default().unix_permissions(*mode);
+            if name.ends_with('/') {
+                writer.add_directory(*name, options).unwrap();
+            } else {
+                writer.start_file(*name, options).unwrap();
+                writer.write_all(contents).unwrap();
+            }
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    /// Unpacks the zip archive `bytes` into a fresh temporary directory, next to the archive file
+    fn unpack_zip_bytes(bytes: &[u8], extraction: &Extraction) -> Result<TempDir, ServerError> {
+        let dir = tempfile::tempdir().unwrap();
+        let archive_path = dir.path().join("server.zip");
+        fs::write(&archive_path, bytes).unwrap();
+        unpack_zip(&archive_path, &dir.path().join("out"), extraction).map(|()| dir)
+    }
+
+    fn extraction() -> Extraction {
+        Extraction::new(&EventSink::default())
+    }
+
+    fn assert_rejected(name: &str, entries: &[Entry]) {
+        let result = unpack(entries, &extraction());
+        assert!(matches!(result, Err(ServerError::ExtractionFailed(_))), "{} was not rejected", name);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn unpacks_server_layout() {
+        let dir = unpack(&[
+            Entry::Dir("vscode-server-linux-x64/"),
+            Entry::Dir("vscode-server-linux-x64/bin/"),
+            Entry::File("vscode-server-linux-x64/bin/code-server", b"#!/bin/sh\n"),
+            Entry::Symlink("vscode-server-linux-x64/bin/remote-cli", "../bin/code-server"),
+            Entry::HardLink("vscode-server-linux-x64/node-copy", "vscode-server-linux-x64/bin/code-server"),
+        ], &extraction()).unwrap();
+
+        let root = unpacked_root(dir.path()).unwrap();
+        assert_eq!(fs::read(root.join("node-copy")).unwrap(), b"#!/bin/sh\n");
+        assert_eq!(fs::read_link(root.join("bin/remote-cli")).unwrap(), Path::new("../bin/code-server"));
+    }
+
+    #[test]
+    fn unpacks_windows_zip_layout() {
+        let archive = zip_archive(&[
+            ("vscode-server-win32-x64/", b"", 0o755),
+            ("vscode-server-win32-x64/bin/", b"", 0o755),
+            ("vscode-server-win32-x64/bin/code-server.cmd", b"@echo off\r\n", 0o755),
+            ("vscode-server-win32-x64/product.json", b"{}", 0o600),
+        ]);
+        let dir = unpack_zip_bytes(&archive, &extraction()).unwrap();
+        let staging_dir = dir.path().join("out");
+        assert_eq!(unpacked_root(&staging_dir).unwrap(), staging_dir.join("vscode-server-win32-x64"));
+
+        let target_dir = dir.path().join("installed");
+        install_unpacked(&staging_dir, &target_dir).unwrap();
+        assert!(install::has_launcher(&target_dir));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |path: &str| fs::metadata(target_dir.join(path)).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode("bin/code-server.cmd"), 0o755);
+            assert_eq!(mode("product.json"), 0o600);
+        }
+    }
+
+    #[test]
+    fn rejects_zip_parent_components() {
+        let archive = zip_archive(&[("vscode-server-win32-x64/../../evil", b"x", 0o644)]);
+        let result = unpack_zip_bytes(&archive, &extraction());
+        assert!(matches!(result, Err(ServerError::ExtractionFailed(message)) if message.starts_with("Unsafe path")));
+    }
+
+    #[test]
+    fn rejects_absolute_paths() {
+        assert_rejected("absolute", &[Entry::File("/tmp/evil", b"x")]);
+    }
+
+    #[test]
+    fn rejects_parent_components() {
+        assert_rejected("parent", &[Entry::File("root/../../evil", b"x")]);
+        assert_rejected("parent-symlink", &[Entry::Symlink("root/link", "../../etc")]);
+    }
+
+    #[test]
+    fn rejects_symlink_chains() {
+        // Each link looks harmless on its own, but `l1` resolves to its own directory on disk
+        assert_rejected("chain-target", &[
+            Entry::Dir("root/"),
+            Entry::Symlink("root/l1", "."),
+            Entry::Symlink("root/escape", "l1/l1/../.."),
+        ]);
+        assert_rejected("chain-path", &[
+            Entry::Dir("root/"),
+            Entry::Symlink("root/l1", "."),
+            Entry::Symlink("root/l1/l1/l1/l1/x", "../../../../.."),
+        ]);
+        assert_rejected("through-symlink", &[
+            Entry::Dir("root/"),
+            Entry::Symlink("root/link", "."),
+            Entry::File("root/link/file", b"x"),
+        ]);
+    }
+
+    #[test]
+    fn rejects_unsafe_hard_links() {
+        assert_rejected("hardlink-absolute", &[Entry::HardLink("root/passwd", "/etc/passwd")]);
+        assert_rejected("hardlink-parent", &[Entry::HardLink("root/passwd", "../etc/passwd")]);
+        assert_rejected("hardlink-to-symlink", &[
+            Entry::Dir("root/"),
+            Entry::Dir("root/a/"),
+            Entry::Symlink("root/a/up", ".."),
+            Entry::HardLink("up", "root/a/up"),
+        ]);
+    }
+
+    #[test]
+    fn rejects_special_files() {
+        assert_rejected("device", &[Entry::Device("root/null")]);
+    }
+
+    #[test]
+    fn enforces_entry_limit() {
+        let entries = [Entry::Dir("root/"), Entry::File("root/a", b"a"), Entry::File("root/b", b"b")];
+        assert!(unpack(&entries, &extraction().with_limits(3, 1024)).is_ok());
+        assert!(unpack(&entries, &extraction().with_limits(2, 1024)).is_err());
+    }
+
+    #[test]
+    fn enforces_size_limit() {
+        let entries = [Entry::File("root/a", b"0123456789"), Entry::File("root/b", b"0123456789")];
+        assert!(unpack(&entries, &extraction().with_limits(10, 20)).is_ok());
+        assert!(unpack(&entries, &extraction().with_limits(10, 19)).is_err());
+    }
+
+    #[test]
+    fn stops_when_cancelled() {
+        let extraction = extraction();
+        drop(extraction.cancel_on_drop());
+        assert!(unpack(&[Entry::File("root/a", b"a")], &extraction).is_err());
+    }
+
+    #[test]
+    fn enforces_size_limit_on_zip_contents() {
+        let contents = [0; 1000];
+        let mut archive = zip_archive(&[("root/a", &contents, 0o644)]);
+        assert!(unpack_zip_bytes(&archive, &extraction().with_limits(10, 1000)).is_ok());
+
+        // Declare an uncompressed size of 1 byte in the local header and the central directory
+        for (signature, offset) in [(b"PK\x03\x04", 22), (b"PK\x01\x02", 24)] {
+            let header = archive.windows(4).position(|window| window == signature).unwrap();
+            archive[header + offset..header + offset + 4].copy_from_slice(&1u32.to_le_bytes());
+        }
+        let result = unpack_zip_bytes(&archive, &extraction().with_limits(10, 100));
+        assert!(matches!(result, Err(ServerError::ExtractionFailed(_))));
+    }
+}
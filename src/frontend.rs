@@ -0,0 +1,305 @@
+// frontend.rs - Version detection from a frontend project's npm metadata
+
+use crate::events::ServerEvent;
+use crate::http::HttpClient;
+use crate::resolver::{self, PackageJson, VscodeConfig};
+use crate::{Platform, ServerConfig, ServerError, ServerInfo};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// npm package name of monaco-vscode-api
+const PACKAGE_NAME: &str = "@codingame/monaco-vscode-api";
+
+/// Reads the installed `monaco-vscode-api` version from a lockfile's contents
+type LockfileReader = fn(&str) -> Option<String>;
+
+/// The `monaco-vscode-api` version declared by a frontend project
+enum FrontendVersion {
+    /// The installed package manifest, which also carries the VSCode release
+    Installed { version: String, vscode: VscodeConfig, source: PathBuf },
+    /// An exact version recorded in a lockfile
+    Locked { version: String, source: PathBuf },
+    /// A version range declared in `package.json`
+    Declared { range: String, source: PathBuf },
+}
+
+/// Detects the VSCode server version matching the `monaco-vscode-api` used by a frontend project.
+///
+/// The project directory is inspected in the following order:
+/// 1. `node_modules/@codingame/monaco-vscode-api/package.json` - provides both the version and
+///    the VSCode commit, so no network request is needed at all.
+/// 2. `package-lock.json`, `pnpm-lock.yaml` or `yarn.lock` - provide the exact installed version,
+///    whose `package.json` is then fetched directly for that tag.
+/// 3. `package.json` - provides the declared version range, which is resolved against the
+///    `monaco-vscode-api` tags like `ServerConfig::monaco_api_version`.
+///
+/// `ServerConfig::endpoints` and `ServerConfig::allow_prerelease` apply to the network lookups.
+///
+/// # Errors
+///
+/// Returns `ServerError::VersionDetectionFailed` if none of the files declare
+/// `@codingame/monaco-vscode-api` or they cannot be parsed, and propagates errors from
+/// the network lookups of steps 2 and 3.
+pub(crate) async fn detect_version(
+    http: &HttpClient,
+    config: &ServerConfig,
+    frontend_dir: &Path,
+    platform: Platform,
+) -> Result<ServerInfo, ServerError> {
+    match read_frontend_version(frontend_dir)? {
+        FrontendVersion::Installed { version, vscode, source } => {
+            http.events().emit(ServerEvent::FrontendVersionDetected { version: version.clone(), source });
+            resolver::server_info(config, format!("v{}", version), vscode, platform)
+        }
+        FrontendVersion::Locked { version, source } => {
+            http.events().emit(ServerEvent::FrontendVersionDetected { version: version.clone(), source });
+            resolver::detect_version_for_tag(http, config, &format!("v{}", version), platform).await
+        }
+        FrontendVersion::Declared { range, source } => {
+            http.events().emit(ServerEvent::FrontendVersionDetected { version: range.clone(), source });
+            resolver::detect_version(http, config, Some(&range), platform).await
+        }
+    }
+}
+
+/// Reads the `monaco-vscode-api` version from the first frontend file that declares it
+fn read_frontend_version(frontend_dir: &Path) -> Result<FrontendVersion, ServerError> {
+    let installed = frontend_dir.join("node_modules").join(PACKAGE_NAME).join("package.json");
+    if installed.exists() {
+        let package_json: PackageJson = serde_json::from_str(&fs::read_to_string(&installed)?)
+            .map_err(|e| parse_error(&installed, e))?;
+        return Ok(FrontendVersion::Installed {
+            version: package_json.version,
+            vscode: package_json.config.vscode,
+            source: installed,
+        });
+    }
+
+    let lockfiles: [(&str, LockfileReader); 3] = [
+        ("package-lock.json", version_from_package_lock),
+        ("pnpm-lock.yaml", version_from_pnpm_lock),
+        ("yarn.lock", version_from_yarn_lock),
+    ];
+    for (name, read_version) in lockfiles {
+        let source = frontend_dir.join(name);
+        if !source.exists() {
+            continue;
+        }
+        if let Some(version) = read_version(&fs::read_to_string(&source)?) {
+            return Ok(FrontendVersion::Locked { version, source });
+        }
+    }
+
+    let source = frontend_dir.join("package.json");
+    if source.exists() {
+        let manifest: serde_json::Value = serde_json::from_str(&fs::read_to_string(&source)?)
+            .map_err(|e| parse_error(&source, e))?;
+        let range = ["dependencies", "devDependencies", "peerDependencies"]
+            .iter()
+            .find_map(|section| manifest[section][PACKAGE_NAME].as_str());
+        if let Some(range) = range {
+            return Ok(FrontendVersion::Declared { range: range.to_string(), source });
+        }
+    }
+
+    Err(ServerError::VersionDetectionFailed(format!(
+        "No {} dependency found in {}",
+        PACKAGE_NAME,
+        frontend_dir.display()
+    )))
+}
+
+/// Reads the installed version from an npm `package-lock.json` (lockfile v1, v2 and v3)
+fn version_from_package_lock(contents: &str) -> Option<String> {
+    let lock: serde_json::Value = serde_json::from_str(contents).ok()?;
+    lock["packages"][format!("node_modules/{}", PACKAGE_NAME)]["version"]
+        .as_str()
+        .or_else(|| lock["dependencies"][PACKAGE_NAME]["version"].as_str())
+        .map(str::to_string)
+}
+
+/// Reads the installed version from a `pnpm-lock.yaml` package key.
+///
+/// Handles the `/name/1.2.3:` (v5), `/name@1.2.3:` (v6) and `name@1.2.3:` (v9) key formats,
+/// including peer dependency suffixes such as `/name/1.2.3_vscode@1.0.0:` (v5) and
+/// `name@1.2.3(vscode@1.0.0):` (v6 and later).
+fn version_from_pnpm_lock(contents: &str) -> Option<String> {
+    contents.lines().find_map(|line| {
+        let key = line.trim().strip_suffix(':')?.trim_matches(|c| c == '\'' || c == '"');
+        let version = key.trim_start_matches('/')
+            .strip_prefix(PACKAGE_NAME)?
+            .strip_prefix(['@', '/'])?;
+        let version = version.split(['(', '_']).next()?;
+        (!version.is_empty()).then(|| version.to_string())
+    })
+}
+
+/// Reads the installed version from a classic (v1) or Berry `yarn.lock`
+fn version_from_yarn_lock(contents: &str) -> Option<String> {
+    let mut in_entry = false;
+    for line in contents.lines() {
+        if !line.starts_with(' ') {
+            in_entry = line.trim_end_matches(':')
+                .split(", ")
+                .map(|spec| spec.trim_matches('"'))
+                .any(|spec| spec.strip_prefix(PACKAGE_NAME).is_some_and(|rest| rest.starts_with('@')));
+        } else if in_entry && let Some(version) = line.trim().strip_prefix("version") {
+            return Some(version.trim_start_matches(':').trim().trim_matches('"').to_string());
+        }
+    }
+    None
+}
+
+/// Builds the error reported for an unparsable frontend file
+fn parse_error(path: &Path, error: serde_json::Error) -> ServerError {
+    ServerError::VersionDetectionFailed(format!("Failed to parse {}: {}", path.display(), error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn package_lock_v1() {
+        let lock = r#"{
+            "lockfileVersion": 1,
+            "dependencies": {
+                "@codingame/monaco-vscode-api": { "version": "16.1.1", "resolved": "https://registry.npmjs.org/x.tgz" }
+            }
+        }"#;
+        assert_eq!(version_from_package_lock(lock).as_deref(), Some("16.1.1"));
+    }
+
+    #[test]
+    fn package_lock_v2_and_v3() {
+        let lock = r#"{
+            "lockfileVersion": 3,
+            "packages": {
+                "": { "dependencies": { "@codingame/monaco-vscode-api": "^16.0.0" } },
+                "node_modules/@codingame/monaco-vscode-api-extensions": { "version": "9.9.9" },
+                "node_modules/@codingame/monaco-vscode-api": { "version": "16.1.1" }
+            }
+        }"#;
+        assert_eq!(version_from_package_lock(lock).as_deref(), Some("16.1.1"));
+        assert_eq!(version_from_package_lock(r#"{ "packages": {} }"#), None);
+        assert_eq!(version_from_package_lock("not json"), None);
+    }
+
+    #[test]
+    fn pnpm_lock_v5() {
+        let lock = "\
+lockfileVersion: 5.4
+
+specifiers:
+  '@codingame/monaco-vscode-api': ^16.0.0
+
+dependencies:
+  '@codingame/monaco-vscode-api': 16.1.1_vscode@1.0.0
+
+packages:
+
+  /@codingame/monaco-vscode-api-extensions/9.9.9:
+    resolution: {integrity: sha512-x}
+
+  /@codingame/monaco-vscode-api/16.1.1_vscode@1.0.0:
+    resolution: {integrity: sha512-y}
+";
+        assert_eq!(version_from_pnpm_lock(lock).as_deref(), Some("16.1.1"));
+    }
+
+    #[test]
+    fn pnpm_lock_v6() {
+        let lock = "\
+lockfileVersion: '6.0'
+
+dependencies:
+  '@codingame/monaco-vscode-api':
+    specifier: ^16.0.0
+    version: 16.1.1(vscode@1.0.0)
+
+packages:
+
+  /@codingame/monaco-vscode-api@16.1.1(vscode@1.0.0):
+    resolution: {integrity: sha512-y}
+";
+        assert_eq!(version_from_pnpm_lock(lock).as_deref(), Some("16.1.1"));
+    }
+
+    #[test]
+    fn pnpm_lock_v9() {
+        let lock = "\
+lockfileVersion: '9.0'
+
+importers:
+
+  .:
+    dependencies:
+      '@codingame/monaco-vscode-api':
+        specifier: ^16.0.0
+        version: 16.1.1
+
+packages:
+
+  '@codingame/monaco-vscode-api@16.1.1':
+    resolution: {integrity: sha512-y}
+";
+        assert_eq!(version_from_pnpm_lock(lock).as_deref(), Some("16.1.1"));
+        assert_eq!(version_from_pnpm_lock("lockfileVersion: '9.0'\n"), None);
+    }
+
+    #[test]
+    fn yarn_lock_classic() {
+        let lock = r#"# THIS IS AN AUTOGENERATED FILE. DO NOT EDIT THIS FILE DIRECTLY.
+# yarn lockfile v1
+
+
+"@codingame/monaco-vscode-api-extensions@^9.0.0":
+  version "9.9.9"
+
+"@codingame/monaco-vscode-api@^16.0.0", "@codingame/monaco-vscode-api@^16.1.0":
+  version "16.1.1"
+  resolved "https://registry.yarnpkg.com/x.tgz"
+  dependencies:
+    "@codingame/monaco-vscode-api" "^16.0.0"
+"#;
+        assert_eq!(version_from_yarn_lock(lock).as_deref(), Some("16.1.1"));
+    }
+
+    #[test]
+    fn yarn_lock_berry() {
+        let lock = r#"__metadata:
+  version: 8
+  cacheKey: 10
+
+"@codingame/monaco-vscode-api@npm:^16.0.0":
+  version: 16.1.1
+  resolution: "@codingame/monaco-vscode-api@npm:16.1.1"
+  languageName: node
+  linkType: hard
+"#;
+        assert_eq!(version_from_yarn_lock(lock).as_deref(), Some("16.1.1"));
+        assert_eq!(version_from_yarn_lock("__metadata:\n  version: 8\n"), None);
+    }
+
+    #[test]
+    fn node_modules_take_precedence_over_lockfiles() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let package_dir = dir.join("node_modules").join(PACKAGE_NAME);
+        fs::create_dir_all(&package_dir).unwrap();
+        fs::write(
+            package_dir.join("package.json"),
+            r#"{ "version": "16.1.1", "config": { "vscode": { "version": "1.98.2", "ref": "1.98.2", "commit": "abc" } } }"#,
+        ).unwrap();
+        fs::write(dir.join("yarn.lock"), "\"@codingame/monaco-vscode-api@^15.0.0\":\n  version \"15.0.0\"\n").unwrap();
+
+        let detected = read_frontend_version(dir);
+        match detected.unwrap() {
+            FrontendVersion::Installed { version, vscode, .. } => {
+                assert_eq!(version, "16.1.1");
+                assert_eq!(vscode.commit, "abc");
+            }
+            _ => panic!("expected the installed package to be used"),
+        }
+    }
+}
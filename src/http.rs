@@ -0,0 +1,288 @@
+// http.rs - HTTP client with retry support
+
+use crate::events::{EventSink, ServerEvent};
+use crate::{ServerConfig, ServerError};
+use reqwest::{Certificate, NoProxy, Proxy};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::future::Future;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Controls how failed HTTP requests are retried.
+///
+/// Delays grow exponentially from `base_delay_ms` (doubling on every attempt) up to
+/// `max_delay_ms`. With `jitter`, each delay is randomly scaled to between 50% and 100%
+/// so that concurrent clients do not retry in lockstep.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetryPolicy {
+        /// The maximum number of attempts per request, including the first one. `1` disables retries.
+    pub max_attempts: u32,
+        /// The delay before the first retry, in milliseconds.
+    pub base_delay_ms: u64,
+        /// The upper bound of the delay between attempts, in milliseconds.
+    pub max_delay_ms: u64,
+        /// If `true`, delays are randomized.
+    pub jitter: bool,
+        /// HTTP status codes that are retried.
+    pub retry_on_status: Vec<u16>,
+        /// If `true`, connection failures, timeouts and interrupted transfers are retried.
+    pub retry_network_errors: bool,
+}
+
+/// Provides default settings for `RetryPolicy`.
+/// - `max_attempts`: `3`
+/// - `base_delay_ms`: `500`
+/// - `max_delay_ms`: `10000`
+/// - `jitter`: `true`
+/// - `retry_on_status`: `[408, 500, 502, 503, 504]`
+/// - `retry_network_errors`: `true`
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay_ms: 500,
+            max_delay_ms: 10_000,
+            jitter: true,
+            retry_on_status: vec![408, 500, 502, 503, 504],
+            retry_network_errors: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns the delay before the attempt following `attempt` (starting at 1).
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponential = self.base_delay_ms.saturating_mul(1 << attempt.saturating_sub(1).min(32));
+        let mut delay_ms = exponential.min(self.max_delay_ms);
+
+        if self.jitter {
+            // Sub-second clock noise is random enough to spread out retries
+            let noise = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.subsec_nanos())
+                .unwrap_or(0);
+            delay_ms = delay_ms / 2 + delay_ms / 2 * u64::from(noise % 1000) / 1000;
+        }
+
+        Duration::from_millis(delay_ms)
+    }
+
+    /// Checks whether a request that failed with `error` may be retried
+    fn retries_error(&self, error: &reqwest::Error) -> bool {
+        self.retry_network_errors && (error.is_connect() || error.is_timeout() || error.is_request() || error.is_body())
+    }
+
+    /// Checks whether a response with `status` may be retried
+    fn retries_status(&self, status: reqwest::StatusCode) -> bool {
+        self.retry_on_status.contains(&status.as_u16())
+    }
+}
+
+/// Proxy, TLS and timeout settings of the HTTP client used for version detection and downloads.
+///
+/// Without an explicit `proxy`, the standard `HTTPS_PROXY`, `HTTP_PROXY`, `ALL_PROXY` and
+/// `NO_PROXY` environment variables are honoured.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+        /// The proxy all requests go through, e.g. `http://proxy.example.com:8080`.
+    pub proxy: Option<String>,
+        /// The user name to authenticate with the proxy.
+    pub proxy_username: Option<String>,
+        /// The password to authenticate with the proxy. It is never serialized.
+    #[serde(skip_serializing)]
+    pub proxy_password: Option<String>,
+        /// Hosts, domains and IP ranges that bypass `proxy`, e.g. `["localhost", ".corp.example.com"]`.
+    /// Falls back to the `NO_PROXY` environment variable when empty.
+    pub no_proxy: Vec<String>,
+        /// A PEM file with additional root certificates to trust, e.g. a corporate CA bundle.
+    pub ca_bundle: Option<PathBuf>,
+        /// How long to wait for a connection to be established, in seconds.
+    pub connect_timeout_secs: u64,
+        /// How long to wait for data on an open connection before giving up, in seconds.
+    pub read_timeout_secs: u64,
+}
+
+/// Provides default settings for `NetworkConfig`.
+/// - `proxy`: `None` (proxy environment variables are used)
+/// - `proxy_username`: `None`
+/// - `proxy_password`: `None`
+/// - `no_proxy`: `[]`
+/// - `ca_bundle`: `None`
+/// - `connect_timeout_secs`: `30`
+/// - `read_timeout_secs`: `300`
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            proxy_username: None,
+            proxy_password: None,
+            no_proxy: Vec::new(),
+            ca_bundle: None,
+            connect_timeout_secs: 30,
+            read_timeout_secs: 300,
+        }
+    }
+}
+
+impl fmt::Debug for NetworkConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NetworkConfig")
+            .field("proxy", &self.proxy)
+            .field("proxy_username", &self.proxy_username)
+            .field("proxy_password", &self.proxy_password.as_ref().map(|_| "<redacted>"))
+            .field("no_proxy", &self.no_proxy)
+            .field("ca_bundle", &self.ca_bundle)
+            .field("connect_timeout_secs", &self.connect_timeout_secs)
+            .field("read_timeout_secs", &self.read_timeout_secs)
+            .finish()
+    }
+}
+
+/// An HTTP client applying the configured `RetryPolicy` to every request
+pub(crate) struct HttpClient {
+    client: reqwest::Client,
+    retry: RetryPolicy,
+    events: EventSink,
+}
+
+impl HttpClient {
+    /// Creates a client for `config`, reporting retries to `events`
+    pub(crate) fn new(config: &ServerConfig, events: &EventSink) -> Result<Self, ServerError> {
+        Ok(Self {
+            client: create_client(&config.network)?,
+            retry: config.retry.clone(),
+            events: events.clone(),
+        })
+    }
+
+    /// Returns the sink events are reported to
+    pub(crate) fn events(&self) -> &EventSink {
+        &self.events
+    }
+
+    /// Sends the request built by `build`, retrying network errors and retryable statuses.
+    ///
+    /// The last response is returned even if its status is unsuccessful.
+    pub(crate) async fn send(
+        &self,
+        build: impl Fn(&reqwest::Client) -> reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, ServerError> {
+        let mut attempt = 1;
+        loop {
+            let request = build(&self.client).build()?;
+            let url = request.url().to_string();
+            let outcome = self.client.execute(request).await;
+
+            let reason = match &outcome {
+                Ok(response) if self.retry.retries_status(response.status()) => {
+                    format!("HTTP {}", response.status())
+                }
+                Err(e) if self.retry.retries_error(e) => e.to_string(),
+                _ => return outcome.map_err(Into::into),
+            };
+            if attempt >= self.retry.max_attempts {
+                return outcome.map_err(Into::into);
+            }
+
+            self.wait_before_retry(url, attempt, reason).await;
+            attempt += 1;
+        }
+    }
+
+    /// Runs `operation`, retrying it when it fails because a transfer was interrupted.
+    ///
+    /// This covers errors raised while streaming a response body, which `send` cannot retry.
+    pub(crate) async fn retry<T, F, Fut>(&self, url: &str, mut operation: F) -> Result<T, ServerError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, ServerError>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match operation().await {
+                Err(ServerError::Network(e))
+                    if self.retry.retry_network_errors && e.is_body() && attempt < self.retry.max_attempts =>
+                {
+                    self.wait_before_retry(url.to_string(), attempt, e.to_string()).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Reports a scheduled retry and waits for its delay
+    async fn wait_before_retry(&self, url: String, attempt: u32, reason: String) {
+        let delay = self.retry.delay(attempt);
+        self.events.emit(ServerEvent::RetryScheduled {
+            url,
+            attempt,
+            max_attempts: self.retry.max_attempts,
+            delay,
+            reason,
+        });
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Creates an HTTP client with the proxy, TLS and timeout settings of `network`
+pub(crate) fn create_client(network: &NetworkConfig) -> Result<reqwest::Client, ServerError> {
+    let mut builder = reqwest::Client::builder()
+        .user_agent("vscode-server-backend")
+        .connect_timeout(Duration::from_secs(network.connect_timeout_secs))
+        .read_timeout(Duration::from_secs(network.read_timeout_secs));
+
+    // Without explicit settings, reqwest reads the proxy environment variables itself
+    if network.proxy.is_some() || network.proxy_username.is_some() || !network.no_proxy.is_empty() {
+        let no_proxy = if network.no_proxy.is_empty() {
+            NoProxy::from_env()
+        } else {
+            NoProxy::from_string(&network.no_proxy.join(","))
+        };
+        for mut proxy in configured_proxies(network)? {
+            if let Some(username) = &network.proxy_username {
+                proxy = proxy.basic_auth(username, network.proxy_password.as_deref().unwrap_or_default());
+            }
+            builder = builder.proxy(proxy.no_proxy(no_proxy.clone()));
+        }
+    }
+
+    if let Some(ca_bundle) = &network.ca_bundle {
+        let pem = fs::read(ca_bundle)?;
+        for certificate in Certificate::from_pem_bundle(&pem)? {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    builder.build().map_err(Into::into)
+}
+
+/// Returns `NetworkConfig::proxy`, or otherwise the proxies named by the environment
+fn configured_proxies(network: &NetworkConfig) -> Result<Vec<Proxy>, ServerError> {
+    if let Some(url) = &network.proxy {
+        return Ok(vec![Proxy::all(url)?]);
+    }
+
+    let mut proxies = Vec::new();
+    if let Some(url) = proxy_from_env("HTTPS_PROXY") {
+        proxies.push(Proxy::https(&url)?);
+    }
+    if let Some(url) = proxy_from_env("HTTP_PROXY") {
+        proxies.push(Proxy::http(&url)?);
+    }
+    if let Some(url) = proxy_from_env("ALL_PROXY") {
+        proxies.push(Proxy::all(&url)?);
+    }
+    Ok(proxies)
+}
+
+/// Reads a proxy URL from the environment variable `var` or its lowercase form
+fn proxy_from_env(var: &str) -> Option<String> {
+    std::env::var(var)
+        .or_else(|_| std::env::var(var.to_ascii_lowercase()))
+        .ok()
+        .filter(|url| !url.is_empty())
+}
@@ -0,0 +1,415 @@
+// install.rs - Completion tracking and repair of installed servers
+
+use crate::cache::unix_now;
+use crate::{extract, resolver};
+use crate::{Endpoints, Platform, Quality, ResolutionSource, ServerError, ServerInfo};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Name of the marker file written into an install directory once the install is complete
+pub(crate) const INSTALL_MARKER: &str = ".install-complete.json";
+
+/// Name of the file in an install directory recording when the server was last started
+const LAST_USED_FILE: &str = ".last-used";
+
+/// A server installed in `server_dir`, as listed by `VscodeServerManager::installed_versions`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstalledServer {
+    /// The install directory of the server.
+    pub path: PathBuf,
+    /// The commit SHA of the VSCode repository the server is built from.
+    pub vscode_commit: String,
+    /// The monaco-vscode-api version the server was installed for, or `"unknown"` if it was
+    /// installed from a local archive.
+    pub monaco_api_version: String,
+    /// The VSCode release version (e.g. `1.98.2`), if known.
+    pub vscode_version: Option<String>,
+    /// The platform the server is built for.
+    pub platform: Platform,
+    /// The release quality of the server.
+    pub quality: Quality,
+    /// When the install completed, in seconds since the Unix epoch.
+    pub installed_at: u64,
+    /// The total size of the server's files, in bytes.
+    pub size_bytes: u64,
+    /// When the server was last started, in seconds since the Unix epoch. `None` if it never was.
+    pub last_used_at: Option<u64>,
+    /// Whether this is the server the manager starts.
+    pub selected: bool,
+}
+
+/// The contents of the install marker: the installed server and how it was verified
+#[derive(Serialize, Deserialize)]
+pub(crate) struct InstallRecord {
+    #[serde(flatten)]
+    pub(crate) info: ServerInfo,
+    /// The SHA-256 hash of the archive the server was extracted from, unknown for adopted
+    /// legacy installs
+    #[serde(default)]
+    pub(crate) sha256: Option<String>,
+    /// When the install completed, in seconds since the Unix epoch
+    pub(crate) installed_at: u64,
+}
+
+/// Checks whether `install_dir` holds a completed install
+pub(crate) fn is_complete(install_dir: &Path) -> bool {
+    install_dir.join(INSTALL_MARKER).is_file()
+}
+
+/// Reads the install marker of `install_dir`, if the install is complete
+pub(crate) fn read_marker(install_dir: &Path) -> Option<InstallRecord> {
+    let json = fs::read_to_string(install_dir.join(INSTALL_MARKER)).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+/// Marks `install_dir` as complete. This must be the last step of an install.
+pub(crate) fn write_marker(install_dir: &Path, info: &ServerInfo, sha256: Option<&str>) -> Result<(), ServerError> {
+    let record = InstallRecord {
+        info: info.clone(),
+        sha256: sha256.map(str::to_string),
+        installed_at: unix_now(),
+    };
+    let json = serde_json::to_string_pretty(&record)
+        .map_err(|e| ServerError::Io(e.into()))?;
+
+    // Write through a temporary file so that the marker never exists with partial contents
+    let temp_path = install_dir.join(format!("{}.tmp", INSTALL_MARKER));
+    fs::write(&temp_path, json)?;
+    fs::rename(&temp_path, install_dir.join(INSTALL_MARKER))?;
+    Ok(())
+}
+
+/// The parts of a server's `product.json` identifying its release
+#[derive(Deserialize)]
+pub(crate) struct ProductJson {
+    pub(crate) commit: Option<String>,
+    pub(crate) version: Option<String>,
+    pub(crate) quality: Option<String>,
+}
+
+/// The outcome of `adopt_legacy`
+#[derive(Default)]
+pub(crate) struct LegacyMigration {
+    /// The legacy directory the install was adopted from, if any
+    pub(crate) adopted: Option<PathBuf>,
+    /// Legacy directories that did not hold a valid server and were removed
+    pub(crate) removed: Vec<PathBuf>,
+}
+
+/// Adopts a server for `info` installed by an earlier version of this crate into `install_dir`.
+///
+/// Earlier versions installed servers for the host platform into `<commit>` directories, without
+/// install marker. Such a directory is adopted if it holds a `bin/code-server` launcher and a
+/// `product.json` naming the VSCode commit and quality of `info`: it is moved to `install_dir` and
+/// marked complete, without a known archive hash. A `<commit>` directory failing this check is
+/// removed. Leftovers in `install_dir` itself are left to `remove_incomplete`.
+///
+/// Must only be called while holding the install lock.
+pub(crate) fn adopt_legacy(install_dir: &Path, info: &ServerInfo) -> Result<LegacyMigration, ServerError> {
+    let mut migration = LegacyMigration::default();
+    let Some(server_dir) = install_dir.parent() else {
+        return Ok(migration);
+    };
+    if Platform::current() != Ok(info.platform) || !resolver::is_commit_sha(&info.vscode_commit) {
+        return Ok(migration);
+    }
+    let legacy_dir = server_dir.join(&info.vscode_commit);
+    if !legacy_dir.is_dir() {
+        return Ok(migration);
+    }
+
+    match legacy_product(&legacy_dir).filter(|(_, product)| matches_product(product, info)) {
+        Some((root, _)) => {
+            if install_dir.exists() {
+                remove_child(server_dir, install_dir)?;
+                migration.removed.push(install_dir.to_path_buf());
+            }
+            fs::rename(&root, install_dir)?;
+            if root != legacy_dir {
+                // The server was unpacked inside the legacy directory
+                remove_child(server_dir, &legacy_dir)?;
+            }
+            write_marker(install_dir, info, None)?;
+            migration.adopted = Some(legacy_dir);
+        }
+        None => {
+            remove_child(server_dir, &legacy_dir)?;
+            migration.removed.push(legacy_dir);
+        }
+    }
+    Ok(migration)
+}
+
+/// Describes the server in the legacy `<commit>` directory `dir`, if it holds one.
+///
+/// The server is taken to be built for the host platform, like every legacy install. Its
+/// `monaco_api_version` is unknown.
+pub(crate) fn legacy_server_info(dir: &Path, endpoints: &Endpoints) -> Option<ServerInfo> {
+    let commit = dir.file_name()?.to_str()?;
+    let platform = Platform::current().ok()?;
+    if !resolver::is_commit_sha(commit) {
+        return None;
+    }
+    let (_, product) = legacy_product(dir)?;
+    if !product.commit.as_deref().is_some_and(|actual| actual.eq_ignore_ascii_case(commit)) {
+        return None;
+    }
+    let quality = match product.quality {
+        Some(quality) => quality.parse().ok()?,
+        None => Quality::Stable,
+    };
+    Some(ServerInfo {
+        monaco_api_version: "unknown".to_string(),
+        vscode_commit: commit.to_string(),
+        vscode_version: product.version,
+        vscode_ref: None,
+        platform,
+        quality,
+        download_url: endpoints.download_url(commit, platform, quality),
+        source: ResolutionSource::Installed,
+        resolved_at: 0,
+        downloaded_from: None,
+    })
+}
+
+/// Finds the server unpacked into the legacy directory `dir` and reads its `product.json`
+fn legacy_product(dir: &Path) -> Option<(PathBuf, ProductJson)> {
+    let root = if has_launcher(dir) { dir.to_path_buf() } else { extract::unpacked_root(dir).ok()? };
+    let json = fs::read_to_string(root.join("product.json")).ok()?;
+    let product = serde_json::from_str(&json).ok()?;
+    Some((root, product))
+}
+
+/// Checks whether `product` names the VSCode commit and quality of `info`
+fn matches_product(product: &ProductJson, info: &ServerInfo) -> bool {
+    let commit_matches = product.commit.as_deref()
+        .is_some_and(|commit| commit.eq_ignore_ascii_case(&info.vscode_commit));
+    let quality_matches = product.quality.as_deref()
+        .is_none_or(|quality| quality == info.quality.as_str());
+    commit_matches && quality_matches
+}
+
+/// Removes the directory `path`, refusing to touch anything that is not a direct child of `server_dir`
+fn remove_child(server_dir: &Path, path: &Path) -> Result<(), ServerError> {
+    let is_child = path.parent() == Some(server_dir)
+        && matches!(path.components().next_back(), Some(Component::Normal(_)));
+    if !is_child {
+        return Err(ServerError::Io(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Refusing to remove {}: not a directory in {}", path.display(), server_dir.display()),
+        )));
+    }
+    fs::remove_dir_all(path)?;
+    Ok(())
+}
+
+/// Checks whether `dir` contains the server's `bin/code-server` launcher
+pub(crate) fn has_launcher(dir: &Path) -> bool {
+    let bin = dir.join("bin");
+    bin.join("code-server").is_file() || bin.join("code-server.cmd").is_file()
+}
+
+/// Records that the server in `install_dir` was started now
+pub(crate) fn mark_used(install_dir: &Path) -> Result<(), ServerError> {
+    fs::write(install_dir.join(LAST_USED_FILE), unix_now().to_string())?;
+    Ok(())
+}
+
+/// Lists the completed installs in `server_dir`, most recently installed first.
+///
+/// `selected` is the install directory of the server in use, if any. Directories without an
+/// install marker, such as staging directories and interrupted installs, are skipped.
+pub(crate) fn list_installed(server_dir: &Path, selected: Option<&Path>) -> Result<Vec<InstalledServer>, ServerError> {
+    let entries = match fs::read_dir(server_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut installed = Vec::new();
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        let Some(InstallRecord { info, installed_at, .. }) = read_marker(&path) else {
+            continue;
+        };
+        if entry.file_name().to_string_lossy() != info.install_dir_name() {
+            continue;
+        }
+
+        let last_used_at = fs::read_to_string(path.join(LAST_USED_FILE)).ok()
+            .and_then(|time| time.trim().parse().ok());
+        installed.push(InstalledServer {
+            size_bytes: dir_size(&path)?,
+            selected: selected == Some(path.as_path()),
+            path,
+            vscode_commit: info.vscode_commit,
+            monaco_api_version: info.monaco_api_version,
+            vscode_version: info.vscode_version,
+            platform: info.platform,
+            quality: info.quality,
+            installed_at,
+            last_used_at,
+        });
+    }
+    installed.sort_by_key(|server| std::cmp::Reverse(server.installed_at));
+    Ok(installed)
+}
+
+/// Sums the sizes of the files in `dir` and its subdirectories, without following symlinks
+fn dir_size(dir: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        size += if metadata.is_dir() { dir_size(&entry.path())? } else { metadata.len() };
+    }
+    Ok(size)
+}
+
+/// Removes the leftovers of interrupted attempts to install `install_dir`: the directory itself
+/// if it lacks the install marker, its checksum record, staging directories and fully downloaded
+/// archives that were never extracted. Partial downloads are kept so that they can be resumed.
+///
+/// Must only be called while holding the install lock. Returns the removed paths.
+pub(crate) fn remove_incomplete(install_dir: &Path) -> Result<Vec<PathBuf>, ServerError> {
+    let mut removed = Vec::new();
+    let (Some(parent), Some(name)) = (install_dir.parent(), install_dir.file_name()) else {
+        return Ok(removed);
+    };
+    let name = name.to_string_lossy();
+
+    if install_dir.exists() && !is_complete(install_dir) {
+        fs::remove_dir_all(install_dir)?;
+        removed.push(install_dir.to_path_buf());
+    }
+
+    let archives = [format!("vscode-server-{}.tar.gz", name), format!("vscode-server-{}.zip", name)];
+    for entry in fs::read_dir(parent)?.filter_map(Result::ok) {
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        let is_staging = file_name.strip_prefix(name.as_ref())
+            .is_some_and(|rest| rest.starts_with('.') && rest.ends_with(".tmp"));
+        let is_stale_checksum = file_name == format!("{}.sha256", name) && !install_dir.exists();
+        if !(is_staging || is_stale_checksum || archives.contains(&file_name)) {
+            continue;
+        }
+
+        // A staging directory may be removed concurrently by the attempt that created it
+        let path = entry.path();
+        let result = if path.is_dir() { fs::remove_dir_all(&path) } else { fs::remove_file(&path) };
+        match result {
+            Ok(()) => removed.push(path),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server_info(commit: &str) -> ServerInfo {
+        ServerInfo {
+            monaco_api_version: "16.1.1".to_string(),
+            vscode_commit: commit.to_string(),
+            vscode_version: Some("1.98.2".to_string()),
+            vscode_ref: None,
+            platform: Platform::current().unwrap(),
+            quality: Quality::Stable,
+            download_url: String::new(),
+            source: ResolutionSource::Resolver,
+            resolved_at: 0,
+            downloaded_from: None,
+        }
+    }
+
+    /// Writes a minimal server for `commit` into `dir`
+    fn write_server(dir: &Path, commit: &str) {
+        fs::create_dir_all(dir.join("bin")).unwrap();
+        fs::write(dir.join("bin").join("code-server"), "#!/bin/sh\n").unwrap();
+        fs::write(dir.join("product.json"), format!(r#"{{ "commit": "{}", "quality": "stable" }}"#, commit)).unwrap();
+    }
+
+    const COMMIT: &str = "863d2581ecda11849ec1d1fdc1f7e6d21c0e1b76";
+    const OTHER_COMMIT: &str = "ddc367ed5c8936efe395cffeec279b04ffd7db78";
+
+    #[test]
+    fn adopts_server_under_former_name() {
+        let temp = tempfile::tempdir().unwrap();
+        let server_dir = temp.path();
+        let info = server_info(COMMIT);
+        write_server(&server_dir.join(COMMIT), COMMIT);
+
+        let install_dir = server_dir.join(info.install_dir_name());
+        let migration = adopt_legacy(&install_dir, &info).unwrap();
+        assert_eq!(migration.adopted, Some(server_dir.join(COMMIT)));
+        assert!(migration.removed.is_empty());
+        assert!(is_complete(&install_dir));
+        assert!(has_launcher(&install_dir));
+        assert!(!server_dir.join(COMMIT).exists());
+        assert_eq!(read_marker(&install_dir).unwrap().sha256, None);
+    }
+
+    #[test]
+    fn leaves_install_without_marker_to_remove_incomplete() {
+        let temp = tempfile::tempdir().unwrap();
+        let server_dir = temp.path();
+        let info = server_info(COMMIT);
+        let install_dir = server_dir.join(info.install_dir_name());
+        write_server(&install_dir, COMMIT);
+
+        let migration = adopt_legacy(&install_dir, &info).unwrap();
+        assert_eq!(migration.adopted, None);
+        assert!(migration.removed.is_empty());
+        assert!(!is_complete(&install_dir));
+        assert_eq!(remove_incomplete(&install_dir).unwrap(), vec![install_dir.clone()]);
+    }
+
+    #[test]
+    fn removes_legacy_dir_of_another_server() {
+        let temp = tempfile::tempdir().unwrap();
+        let server_dir = temp.path();
+        let info = server_info(COMMIT);
+        write_server(&server_dir.join(COMMIT), OTHER_COMMIT);
+
+        let migration = adopt_legacy(&server_dir.join(info.install_dir_name()), &info).unwrap();
+        assert_eq!(migration.adopted, None);
+        assert_eq!(migration.removed, vec![server_dir.join(COMMIT)]);
+        assert!(!server_dir.join(COMMIT).exists());
+    }
+
+    #[test]
+    fn describes_legacy_servers() {
+        let temp = tempfile::tempdir().unwrap();
+        let server_dir = temp.path();
+        write_server(&server_dir.join(COMMIT), COMMIT);
+        write_server(&server_dir.join(OTHER_COMMIT), COMMIT);
+        write_server(&server_dir.join("not-a-commit"), COMMIT);
+
+        let endpoints = Endpoints::default();
+        let info = legacy_server_info(&server_dir.join(COMMIT), &endpoints).unwrap();
+        assert_eq!(info.vscode_commit, COMMIT);
+        assert_eq!(info.monaco_api_version, "unknown");
+        assert_eq!(info.quality, Quality::Stable);
+        assert_eq!(info.source, ResolutionSource::Installed);
+        assert!(legacy_server_info(&server_dir.join(OTHER_COMMIT), &endpoints).is_none());
+        assert!(legacy_server_info(&server_dir.join("not-a-commit"), &endpoints).is_none());
+    }
+
+    #[test]
+    fn only_removes_direct_children() {
+        let temp = tempfile::tempdir().unwrap();
+        let server_dir = temp.path().join("servers");
+        fs::create_dir_all(server_dir.join("child")).unwrap();
+
+        assert!(remove_child(&server_dir, &server_dir.join("..")).is_err());
+        assert!(remove_child(&server_dir, &server_dir.join("child").join("..").join("..")).is_err());
+        assert!(remove_child(&server_dir, temp.path()).is_err());
+        assert!(server_dir.exists());
+        remove_child(&server_dir, &server_dir.join("child")).unwrap();
+        assert!(!server_dir.join("child").exists());
+    }
+}
@@ -5,8 +5,8 @@
 //! 
 //! ## Features
 //! - `embed`: Enables embedding the VSCode server binary directly into your application.
-//!            When this feature is active, the server can be extracted and run without
-//!            needing a separate download step at runtime, unless overridden.
+//!   When this feature is active, the server can be extracted and run without
+//!   needing a separate download step at runtime, unless overridden.
 //! 
 //! ## Quick Start
 //! 
@@ -33,11 +33,25 @@
 //! ```
 
 // Module declarations - these correspond to other files in src/
+mod cache;
 mod download;
+mod events;
+mod extract;
+mod frontend;
+mod http;
+mod install;
 mod platform;
+mod resolver;
+#[cfg(test)]
+mod test_support;
 
 // Re-export commonly used types at the crate root
-pub use platform::Platform;
+pub use cache::ResolutionSource;
+pub use events::{EventSink, ServerEvent};
+pub use http::{NetworkConfig, RetryPolicy};
+pub use install::InstalledServer;
+pub use platform::{Platform, Quality};
+pub use resolver::{Endpoints, GitHubResolver, VersionResolver};
 
 // Standard library imports
 use std::path::{Path, PathBuf};
@@ -88,13 +102,37 @@ pub enum ServerError {
     /// An error occurred during the download process (e.g., HTTP error status).
     #[error("Download failed: {0}")]
     DownloadFailed(String),
+    
+    /// The downloaded server archive does not match its expected SHA-256 hash.
+    #[error("Checksum mismatch: expected SHA-256 {expected}, got {actual}")]
+    ChecksumMismatch { expected: String, actual: String },
+    
+    /// GitHub rejected a request because its API rate limit was exceeded.
+    ///
+    /// `reset_at` is the Unix time (in seconds) at which the limit resets, if GitHub reported it.
+    #[error("GitHub API rate limit exceeded{}; set GITHUB_TOKEN or ServerConfig::github_token to raise the limit", rate_limit_reset(*.reset_at))]
+    RateLimited { reset_at: Option<u64> },
+    
+    /// The server directory cannot be created or written to.
+    #[error("Server directory {} is not writable: {source}", .path.display())]
+    NotWritable { path: PathBuf, source: std::io::Error },
+    
+    /// There is not enough free disk space to download and extract the server.
+    ///
+    /// `required` is an estimate based on the archive size; `available` is the space left on the
+    /// file system holding `path`.
+    #[error(
+        "Not enough disk space in {}: {} more bytes needed ({} required, {} available)",
+        .path.display(), .required.saturating_sub(*.available), .required, .available
+    )]
+    InsufficientSpace { path: PathBuf, required: u64, available: u64 },
 }
 
 /// Configuration for the VSCode server instance.
 ///
 /// This struct allows customization of various server parameters such as port, host,
 /// installation directory, and command-line arguments.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct ServerConfig {
         /// The port number on which the server will listen.
     pub port: u16,
@@ -108,6 +146,106 @@ pub struct ServerConfig {
     pub disable_telemetry: bool,
         /// An optional connection token for securing the server.
     pub connection_token: Option<String>,
+        /// Pins the `monaco-vscode-api` release the server is matched against.
+    ///
+    /// Accepts an exact version (e.g. `16.1.1`) or a semver range (e.g. `^16.0`), in which
+    /// case the highest matching tag is used. When `None`, the highest release is used.
+    /// Ignored when `frontend_dir` is set.
+    pub monaco_api_version: Option<String>,
+        /// The root of a frontend project depending on `@codingame/monaco-vscode-api`.
+    ///
+    /// When set, the server version is taken from the project's installed package
+    /// (`node_modules`), its lockfile (`package-lock.json`, `pnpm-lock.yaml`, `yarn.lock`)
+    /// or its `package.json`, keeping the server in step with the frontend.
+    pub frontend_dir: Option<PathBuf>,
+        /// If `true`, pre-release `monaco-vscode-api` tags (e.g. `17.0.0-next.1`) may be selected
+    /// when resolving the latest release or a version range.
+    #[serde(default)]
+    pub allow_prerelease: bool,
+        /// The base URLs used to resolve and download the server.
+    #[serde(default)]
+    pub endpoints: Endpoints,
+        /// If `true`, no network requests are made and the server is resolved from `server_dir`:
+    /// the last successfully resolved version if still installed, otherwise the newest installed server.
+    /// Installs matching `monaco_api_version` are preferred, and an exact version pin is never
+    /// replaced by another version.
+    #[serde(default = "default_offline")]
+    pub offline: bool,
+        /// How long, in seconds, a resolved server version is reused from the cache in `server_dir`
+    /// before the resolver is queried again. `0` disables the cache.
+    #[serde(default = "default_resolution_cache_ttl_secs")]
+    pub resolution_cache_ttl_secs: u64,
+        /// If `true`, the resolution cache is ignored and the version is always resolved again.
+    #[serde(default)]
+    pub force_refresh: bool,
+        /// A GitHub token used to authenticate version detection requests, raising the API rate limit.
+    ///
+    /// It is sent to the configured `endpoints`, whichever host they point to. When `None`, the
+    /// `GITHUB_TOKEN` environment variable is used instead, but only for requests to
+    /// `api.github.com` and `raw.githubusercontent.com`. It is never serialized and is redacted
+    /// in `Debug` output.
+    #[serde(default, skip_serializing)]
+    pub github_token: Option<String>,
+        /// The VSCode release quality to download: stable, insider or exploration.
+    ///
+    /// Use `Quality::Insider` when `monaco-vscode-api` pre-releases are built on insiders commits.
+    /// Servers of different qualities are installed side by side.
+    #[serde(default)]
+    pub quality: Quality,
+        /// The expected SHA-256 hash (hex) of the server archive. Overrides `checksum_file`
+    /// and the checksum published by the update service. As it matches a single archive,
+    /// `ensure_servers_for` rejects it when installing servers for several platforms.
+    #[serde(default)]
+    pub expected_sha256: Option<String>,
+        /// A checksum lockfile with lines of the form `<sha256>  <commit>/<flavor>/<quality>`,
+    /// e.g. `<sha256>  <commit>/server-linux-x64/stable`. When set, the archive must have an entry in it.
+    #[serde(default)]
+    pub checksum_file: Option<PathBuf>,
+        /// How failed HTTP requests for version detection and downloads are retried.
+    #[serde(default)]
+    pub retry: RetryPolicy,
+        /// Proxy, TLS and timeout settings applied to version detection and downloads.
+    #[serde(default)]
+    pub network: NetworkConfig,
+        /// Mirrors to download the server archive from, tried in order before `Endpoints::download_url_template`.
+    ///
+    /// Each entry is a URL template with `{commit}`, `{flavor}` and `{quality}` placeholders,
+    /// e.g. `https://artifacts.example.com/vscode/{commit}/{flavor}-{quality}`.
+    #[serde(default)]
+    pub mirrors: Vec<String>,
+        /// If `true`, tar.gz archives are unpacked while they download instead of being saved first,
+    /// avoiding a second copy on disk. The archive is still verified before the install is moved into place,
+    /// but an interrupted download restarts from the beginning. Zip archives are always saved first.
+    #[serde(default)]
+    pub streaming_extraction: bool,
+}
+
+impl std::fmt::Debug for ServerConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ServerConfig")
+            .field("port", &self.port)
+            .field("host", &self.host)
+            .field("args", &self.args)
+            .field("server_dir", &self.server_dir)
+            .field("disable_telemetry", &self.disable_telemetry)
+            .field("connection_token", &self.connection_token)
+            .field("monaco_api_version", &self.monaco_api_version)
+            .field("frontend_dir", &self.frontend_dir)
+            .field("allow_prerelease", &self.allow_prerelease)
+            .field("endpoints", &self.endpoints)
+            .field("offline", &self.offline)
+            .field("resolution_cache_ttl_secs", &self.resolution_cache_ttl_secs)
+            .field("force_refresh", &self.force_refresh)
+            .field("github_token", &self.github_token.as_ref().map(|_| "<redacted>"))
+            .field("quality", &self.quality)
+            .field("expected_sha256", &self.expected_sha256)
+            .field("checksum_file", &self.checksum_file)
+            .field("retry", &self.retry)
+            .field("network", &self.network)
+            .field("mirrors", &self.mirrors)
+            .field("streaming_extraction", &self.streaming_extraction)
+            .finish()
+    }
 }
 
 /// Provides default settings for `ServerConfig`.
@@ -117,6 +255,21 @@ pub struct ServerConfig {
 /// - `server_dir`: A platform-specific cache directory or `./vscode-server`.
 /// - `disable_telemetry`: `true`
 /// - `connection_token`: `None`
+/// - `monaco_api_version`: `None` (latest release)
+/// - `frontend_dir`: `None`
+/// - `allow_prerelease`: `false`
+/// - `endpoints`: `Endpoints::default()` (GitHub and the VSCode update service)
+/// - `offline`: `true` if the `VSCODE_SERVER_OFFLINE` environment variable is set to `1`, `true` or `yes`
+/// - `resolution_cache_ttl_secs`: `3600` (one hour)
+/// - `force_refresh`: `false`
+/// - `github_token`: `None` (the `GITHUB_TOKEN` environment variable is used if set)
+/// - `quality`: `Quality::Stable`
+/// - `expected_sha256`: `None`
+/// - `checksum_file`: `None`
+/// - `retry`: `RetryPolicy::default()` (3 attempts with exponential backoff)
+/// - `network`: `NetworkConfig::default()` (proxy from the environment, 30s connect and 300s read timeouts)
+/// - `mirrors`: `[]`
+/// - `streaming_extraction`: `false`
 impl Default for ServerConfig {
     fn default() -> Self {
         Self {
@@ -126,6 +279,21 @@ impl Default for ServerConfig {
             server_dir: default_server_dir(),
             disable_telemetry: true,
             connection_token: None,
+            monaco_api_version: None,
+            frontend_dir: None,
+            allow_prerelease: false,
+            endpoints: Endpoints::default(),
+            offline: default_offline(),
+            resolution_cache_ttl_secs: default_resolution_cache_ttl_secs(),
+            force_refresh: false,
+            github_token: None,
+            quality: Quality::Stable,
+            expected_sha256: None,
+            checksum_file: None,
+            retry: RetryPolicy::default(),
+            network: NetworkConfig::default(),
+            mirrors: Vec::new(),
+            streaming_extraction: false,
         }
     }
 }
@@ -133,17 +301,63 @@ impl Default for ServerConfig {
 /// Holds information about the detected or embedded VSCode server.
 ///
 /// This includes the version of `monaco-vscode-api` it's compatible with,
-/// the specific VSCode commit SHA, the target platform, and the download URL.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// the specific VSCode commit SHA and release version, the target platform, and the download URL.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ServerInfo {
         /// The version of `monaco-vscode-api` this server is intended to work with.
     pub monaco_api_version: String,
         /// The specific commit SHA of the VSCode repository this server is built from.
     pub vscode_commit: String,
+        /// The human-readable VSCode release version (e.g. `1.98.2`), if known.
+    #[serde(default)]
+    pub vscode_version: Option<String>,
+        /// The VSCode git ref (tag or branch) the commit was taken from, if known.
+    #[serde(default)]
+    pub vscode_ref: Option<String>,
         /// The platform (OS and architecture) for which this server is intended.
     pub platform: Platform,
+        /// The release quality (stable, insider or exploration) of this server.
+    #[serde(default)]
+    pub quality: Quality,
         /// The direct URL from which this version of the server can be downloaded.
     pub download_url: String,
+        /// Where this information came from, e.g. the local cache when resolved offline.
+    #[serde(default)]
+    pub source: ResolutionSource,
+        /// When this version was resolved, in seconds since the Unix epoch.
+    #[serde(default)]
+    pub resolved_at: u64,
+        /// The URL the archive was downloaded from by `ensure_server`: one of `ServerConfig::mirrors`
+    /// or `download_url`. `None` if the server was already installed.
+    #[serde(default)]
+    pub downloaded_from: Option<String>,
+}
+
+impl ServerInfo {
+    /// Returns the name of the directory within `server_dir` this server is installed in.
+    ///
+    /// This is `<commit>-<platform>` for stable servers (e.g. `<commit>-linux-x64`), and
+    /// `<commit>-<platform>-<quality>` otherwise, so servers of different platforms and
+    /// qualities never collide.
+    pub fn install_dir_name(&self) -> String {
+        match self.quality {
+            Quality::Stable => format!("{}-{}", self.vscode_commit, self.platform.as_str()),
+            quality => format!("{}-{}-{}", self.vscode_commit, self.platform.as_str(), quality),
+        }
+    }
+
+    /// Returns the same server release for `platform`, with its download URL built from `endpoints`.
+    ///
+    /// The resolved VSCode commit does not depend on the platform, so a resolution can be
+    /// reused for every platform.
+    pub(crate) fn for_platform(&self, platform: Platform, endpoints: &Endpoints) -> ServerInfo {
+        ServerInfo {
+            platform,
+            download_url: endpoints.download_url(&self.vscode_commit, platform, self.quality),
+            downloaded_from: None,
+            ..self.clone()
+        }
+    }
 }
 
 /// Manages the lifecycle of a VSCode server instance.
@@ -159,6 +373,8 @@ pub struct VscodeServerManager {
     info: Option<ServerInfo>,
     process: Arc<Mutex<Option<Child>>>,
     server_path: Option<PathBuf>,
+    resolver: Arc<dyn VersionResolver>,
+    events: EventSink,
 }
 
 impl VscodeServerManager {
@@ -193,6 +409,8 @@ impl VscodeServerManager {
             info: None,
             process: Arc::new(Mutex::new(None)),
             server_path: None,
+            resolver: Arc::new(GitHubResolver),
+            events: EventSink::default(),
         })
     }
     
@@ -201,9 +419,15 @@ impl VscodeServerManager {
     /// This method performs the following steps:
     /// 1. If the `embed` feature is enabled, it first tries to extract an embedded server.
     /// 2. If no embedded server is found or the feature is disabled, it attempts to detect
-    ///    the latest compatible VSCode server version.
-    /// 3. It checks if this version is already present in the configured `server_dir`.
-    /// 4. If not present, it downloads and extracts the server.
+    ///    the compatible VSCode server version using the configured `VersionResolver`
+    ///    (by default `GitHubResolver`). A previous resolution persisted in `server_dir` is
+    ///    reused instead while it is younger than `ServerConfig::resolution_cache_ttl_secs`.
+    ///    If `ServerConfig::offline` is set, or the network is unreachable or rate limited, the version is
+    ///    instead taken from the last successful resolution persisted in `server_dir` or the
+    ///    newest installed server; `ServerInfo::source` reports when this happened.
+    /// 3. It checks if this version is already completely installed in the configured `server_dir`.
+    /// 4. If not, it adopts a matching server installed by an earlier version of this crate,
+    ///    or downloads and extracts the server, first removing what an interrupted install left behind.
     ///
     /// This method must be called before `start()` if the server's presence is not guaranteed.
     /// It is an asynchronous operation due to potential network I/O.
@@ -212,25 +436,172 @@ impl VscodeServerManager {
     ///
     /// Returns `ServerError` if:
     /// - Version detection fails (`ServerError::VersionDetectionFailed`).
+    /// - GitHub's API rate limit is exceeded and no cached server is available (`ServerError::RateLimited`).
+    /// - `server_dir` is not writable (`ServerError::NotWritable`).
+    /// - The disk lacks space for the archive and the extracted server (`ServerError::InsufficientSpace`).
     /// - Downloading fails (`ServerError::Network`, `ServerError::DownloadFailed`).
+    /// - The downloaded archive fails verification (`ServerError::ChecksumMismatch`).
     /// - Extraction fails (`ServerError::ExtractionFailed`, `ServerError::Io`).
     /// - The platform is unsupported (`ServerError::UnsupportedPlatform`).
     // Ensure server is available (download if needed)
-    pub async fn ensure_server(&mut self) -> Result<(), ServerError> {        
-        // Otherwise download
-        let info = download::detect_version().await?;
+    pub async fn ensure_server(&mut self) -> Result<(), ServerError> {
+        let events = self.events.clone();
+        self.ensure_server_with_events(&events).await
+    }
+
+    /// Ensures the VSCode server is available like `ensure_server`, reporting progress to `events`
+    /// instead of the sink set with `set_event_sink`.
+    ///
+    /// Events cover the version resolution, the download progress, the extraction and the final
+    /// install location. See `ServerEvent` for the full list.
+    ///
+    /// # Errors
+    ///
+    /// Fails in the same cases as `ensure_server`.
+    pub async fn ensure_server_with_events(&mut self, events: &EventSink) -> Result<(), ServerError> {
+        let platform = Platform::current()
+            .map_err(ServerError::UnsupportedPlatform)?;
+        let info = self.resolve(platform, events).await?;
         self.info = Some(info.clone());
         
-        let server_path = self.config.server_dir.join(&info.vscode_commit);
+        let server_path = self.config.server_dir.join(info.install_dir_name());
         
-        if !server_path.exists() {
-            download::download_server(&info, &self.config.server_dir).await?;
+        if install::is_complete(&server_path) {
+            events.emit(ServerEvent::Installed { path: server_path.clone() });
+        } else {
+            let downloaded_from = download::download_server_with_events(&info, &self.config, events).await?;
+            self.info = Some(ServerInfo { downloaded_from, ..info });
         }
         
         self.server_path = Some(server_path);
         Ok(())
     }
     
+    /// Ensures the VSCode server for another `platform` is installed in `server_dir`, e.g. to
+    /// pre-stage servers for every bundle of an application.
+    ///
+    /// The server is resolved and downloaded like in `ensure_server`, and installed next to the
+    /// host's server (see `ServerInfo::install_dir_name`). It does not become the server this
+    /// manager starts, and the last resolution persisted for the host is left untouched.
+    ///
+    /// Returns information about the installed server.
+    ///
+    /// # Errors
+    ///
+    /// Fails in the same cases as `ensure_server`.
+    pub async fn ensure_server_for(&self, platform: Platform) -> Result<ServerInfo, ServerError> {
+        let mut installed = self.ensure_servers_for(&[platform]).await?;
+        Ok(installed.remove(0))
+    }
+
+    /// Ensures the VSCode servers for several `platforms` are installed in `server_dir`, like
+    /// `ensure_server_for`.
+    ///
+    /// The server version is resolved only once, for the first platform; the servers for the
+    /// other platforms are the same release with their own download URLs. Servers are installed
+    /// in order, stopping at the first failure.
+    ///
+    /// Returns information about the installed servers, in the order of `platforms`.
+    ///
+    /// # Errors
+    ///
+    /// Fails in the same cases as `ensure_server`, and with `ServerError::DownloadFailed` if
+    /// `ServerConfig::expected_sha256` is set while servers for more than one platform are
+    /// requested: it can only match one archive, so list the archives in
+    /// `ServerConfig::checksum_file` instead.
+    pub async fn ensure_servers_for(&self, platforms: &[Platform]) -> Result<Vec<ServerInfo>, ServerError> {
+        let Some(&first) = platforms.first() else {
+            return Ok(Vec::new());
+        };
+        if self.config.expected_sha256.is_some() && platforms.iter().any(|&platform| platform != first) {
+            return Err(ServerError::DownloadFailed(
+                "expected_sha256 matches a single archive and cannot verify servers for several platforms; use checksum_file instead".to_string()
+            ));
+        }
+        let resolved = self.resolve(first, &self.events).await?;
+
+        let mut installed = Vec::with_capacity(platforms.len());
+        for &platform in platforms {
+            let info = if platform == first {
+                resolved.clone()
+            } else {
+                resolved.for_platform(platform, &self.config.endpoints)
+            };
+            let downloaded_from = download::download_server_with_events(&info, &self.config, &self.events).await?;
+            installed.push(ServerInfo { downloaded_from, ..info });
+        }
+        Ok(installed)
+    }
+    
+    /// Installs the VSCode server from a pre-downloaded `.tar.gz` or `.zip` archive, without any
+    /// network access, e.g. on air-gapped machines.
+    ///
+    /// The archive is extracted and verified like a download (including `ServerConfig::expected_sha256`
+    /// and `ServerConfig::checksum_file`) and installed under `server_dir`. The VSCode commit is read
+    /// from the archive's `product.json`; if `expected_commit` is given, the archive must match it.
+    /// If the archive is for the host platform, it becomes the server this manager starts.
+    ///
+    /// Returns information about the installed server. Its `monaco_api_version` is `"unknown"`,
+    /// as the archive does not record it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ServerError` if:
+    /// - `server_dir` is not writable (`ServerError::NotWritable`).
+    /// - The disk lacks space for the extracted server (`ServerError::InsufficientSpace`).
+    /// - The archive cannot be read or extracted, or does not contain a server (`ServerError::ExtractionFailed`, `ServerError::Io`).
+    /// - The archive's commit differs from `expected_commit` (`ServerError::ExtractionFailed`).
+    /// - The platform of a tar.gz archive cannot be told from its top-level directory (`ServerError::ExtractionFailed`).
+    /// - The archive does not match the configured checksum (`ServerError::ChecksumMismatch`).
+    pub async fn install_from_archive(
+        &mut self,
+        archive_path: impl AsRef<Path>,
+        expected_commit: Option<&str>,
+    ) -> Result<ServerInfo, ServerError> {
+        let info = download::install_from_archive(archive_path.as_ref(), expected_commit, &self.config, &self.events).await?;
+        if Platform::current() == Ok(info.platform) {
+            self.server_path = Some(self.config.server_dir.join(info.install_dir_name()));
+            self.info = Some(info.clone());
+        }
+        Ok(info)
+    }
+    
+    /// Resolves the server for `platform`, from the cache or the configured `VersionResolver`
+    async fn resolve(&self, platform: Platform, events: &EventSink) -> Result<ServerInfo, ServerError> {
+        events.emit(ServerEvent::ResolveStarted { platform, quality: self.config.quality });
+        let info = if self.config.offline {
+            cache::resolve_offline(&self.config, platform, events)?
+        } else if let Some(info) = cache::load_fresh(&self.config, platform) {
+            info
+        } else {
+            match self.resolver.resolve(&self.config, platform, events).await {
+                Ok(mut info) => {
+                    info.resolved_at = cache::unix_now();
+                    // Only the host's resolution is persisted, as the cache holds a single record
+                    if Platform::current() == Ok(platform)
+                        && let Err(e) = cache::save_last_resolved(&self.config, &info)
+                    {
+                        events.emit(ServerEvent::Warning(format!("Failed to persist resolved server info: {}", e)));
+                    }
+                    info
+                }
+                Err(ServerError::Network(e)) if e.is_connect() || e.is_timeout() => {
+                    events.emit(ServerEvent::Warning(format!(
+                        "Network unreachable ({}), resolving the server from the local cache", e
+                    )));
+                    cache::resolve_offline(&self.config, platform, events)?
+                }
+                Err(e @ ServerError::RateLimited { .. }) => {
+                    events.emit(ServerEvent::Warning(format!("{}, resolving the server from the local cache", e)));
+                    cache::resolve_offline(&self.config, platform, events).map_err(|_| e)?
+                }
+                Err(e) => return Err(e),
+            }
+        };
+        events.emit(ServerEvent::Resolved { info: info.clone() });
+        Ok(info)
+    }
+    
         /// Starts the VSCode server process.
     ///
     /// Before calling `start`, `ensure_server` should typically be called to make sure
@@ -287,6 +658,9 @@ match &self.config.connection_token {
             .map_err(|e| ServerError::StartFailed(e.to_string()))?;
         
         *process_guard = Some(child);
+        if let Err(e) = install::mark_used(server_path) {
+            self.events.emit(ServerEvent::Warning(format!("Failed to record when the server was last used: {}", e)));
+        }
         
         // Wait for server to initialize
         tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
@@ -355,6 +729,27 @@ match &self.config.connection_token {
         self.info.as_ref()
     }
     
+    /// Lists the servers installed in `server_dir`, most recently installed first.
+    ///
+    /// Each entry describes an install's VSCode commit and version, the monaco-vscode-api version
+    /// it was installed for, its platform and quality, when it was installed and last started,
+    /// and its size on disk. The server this manager starts is marked as `selected`; before
+    /// `ensure_server` has run, that is the last resolved server. Incomplete installs are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ServerError::Io` if `server_dir` or an install directory cannot be read.
+    pub async fn installed_versions(&self) -> Result<Vec<InstalledServer>, ServerError> {
+        let server_dir = self.config.server_dir.clone();
+        let server_path = self.server_path.clone();
+        tokio::task::spawn_blocking(move || {
+            let selected = server_path.or_else(|| cache::last_resolved_dir(&server_dir));
+            install::list_installed(&server_dir, selected.as_deref())
+        })
+        .await
+        .map_err(|e| ServerError::Io(std::io::Error::other(e)))?
+    }
+    
     // Helper to get executable path
     fn get_executable_path(&self, server_path: &Path) -> Result<PathBuf, ServerError> {
         let exe = if cfg!(target_os = "windows") {
@@ -374,16 +769,30 @@ match &self.config.connection_token {
     pub fn config(&self) -> &ServerConfig {
         &self.config
     }
+    
+    /// Replaces the `VersionResolver` used by `ensure_server` to determine the server version.
+    ///
+    /// The default is `GitHubResolver`.
+    pub fn set_resolver(&mut self, resolver: Arc<dyn VersionResolver>) {
+        self.resolver = resolver;
+    }
+    
+    /// Sets the sink receiving `ServerEvent`s (progress, warnings and retries) reported by `ensure_server`.
+    ///
+    /// By default events are discarded.
+    pub fn set_event_sink(&mut self, events: EventSink) {
+        self.events = events;
+    }
 }
 
 /// Ensures the server process is stopped when the `VscodeServerManager` goes out of scope.
 // Cleanup on drop
 impl Drop for VscodeServerManager {
     fn drop(&mut self) {
-        if let Ok(mut process_guard) = self.process.try_lock() {
-            if let Some(mut child) = process_guard.take() {
-                let _ = child.kill();
-            }
+        if let Ok(mut process_guard) = self.process.try_lock()
+            && let Some(mut child) = process_guard.take()
+        {
+            let _ = child.kill();
         }
     }
 }
@@ -473,6 +882,24 @@ impl TauriVscodeServer {
         manager.info().cloned().ok_or(ServerError::ServerNotFound)
     }
     
+    /// Initializes the VSCode server like `initialize`, reporting progress to `events`.
+    ///
+    /// Useful to forward download and extraction progress to the Tauri frontend.
+    ///
+    /// # Errors
+    ///
+    /// See `initialize`.
+    pub async fn initialize_with_events(&self, events: &EventSink) -> Result<ServerInfo, ServerError> {
+        let mut manager = self.manager.lock().await;
+        manager.ensure_server_with_events(events).await?;
+        
+        if self.config.auto_start {
+            manager.start().await?;
+        }
+        
+        manager.info().cloned().ok_or(ServerError::ServerNotFound)
+    }
+    
         /// Returns the URL where the VSCode server is expected to be listening.
     ///
     /// This is an asynchronous operation as it requires locking the underlying manager.
@@ -484,8 +911,8 @@ impl TauriVscodeServer {
         /// Returns a JSON representation of the server's information and configuration.
     ///
     /// This is useful for providing server details to a Tauri frontend.
-    /// The JSON object includes `serverUrl`, `monacoApiVersion`, `vscodeCommit`,
-    /// `platform`, and `serviceConfig` (with `baseUrl` and `connectionToken`).
+    /// The JSON object includes `serverUrl`, `monacoApiVersion`, `vscodeCommit`, `vscodeVersion`,
+    /// `vscodeRef`, `platform`, `quality`, and `serviceConfig` (with `baseUrl` and `connectionToken`).
     ///
     /// # Errors
     ///
@@ -498,7 +925,10 @@ impl TauriVscodeServer {
             "serverUrl": manager.url(),
             "monacoApiVersion": info.monaco_api_version,
             "vscodeCommit": info.vscode_commit,
+            "vscodeVersion": info.vscode_version,
+            "vscodeRef": info.vscode_ref,
             "platform": info.platform.to_string(),
+            "quality": info.quality.to_string(),
             "serviceConfig": {
                 "baseUrl": manager.url(),
                 "connectionToken": manager.config().connection_token,
@@ -558,5 +988,28 @@ fn default_server_dir() -> PathBuf {
     PathBuf::from("./vscode-server")
 }
 
+/// Reads the default offline mode from the `VSCODE_SERVER_OFFLINE` environment variable.
+fn default_offline() -> bool {
+    std::env::var("VSCODE_SERVER_OFFLINE")
+        .is_ok_and(|value| matches!(value.to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+}
+
+/// Describes when a GitHub rate limit resets, for `ServerError::RateLimited`.
+fn rate_limit_reset(reset_at: Option<u64>) -> String {
+    match reset_at {
+        Some(reset_at) => format!(
+            " (resets in {}s, at Unix time {})",
+            reset_at.saturating_sub(cache::unix_now()),
+            reset_at
+        ),
+        None => String::new(),
+    }
+}
+
+/// The default TTL of the resolution cache: one hour.
+fn default_resolution_cache_ttl_secs() -> u64 {
+    3600
+}
+
 // Re-exports for convenience
-pub use download::download_server;
\ No newline at end of file
+pub use download::{download_server, download_server_with_events};
\ No newline at end of file
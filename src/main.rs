@@ -1,6 +1,11 @@
 use clap::{Parser, Subcommand};
-use monaco_vscode_server::{ServerConfig, VscodeServerManager};
+use monaco_vscode_server::{
+    EventSink, InstalledServer, Platform, Quality, ResolutionSource, ServerConfig, ServerEvent, ServerInfo,
+    VscodeServerManager,
+};
+use std::io::Write;
 use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Parser)]
 #[command(author, version, about = "A CLI tool to manage the VSCode Server for monaco-vscode-api.", long_about = "This utility allows you to download, start, and manage the VSCode Server backend required by the monaco-vscode-api library. It simplifies the process of setting up the server environment.")]
@@ -19,6 +24,11 @@ enum Commands {
     /// Ensures the VSCode server is downloaded to the specified directory.
     /// If the server (matching the version required by the embedded monaco-vscode-api) is already present, this command does nothing.
     Download(DownloadArgs),
+    /// Installs the VSCode server from a pre-downloaded .tar.gz or .zip archive, without network access.
+    Install(InstallArgs),
+    /// Lists the VSCode servers installed in the server directory.
+    /// The most recently resolved server is marked with an asterisk.
+    List(ListArgs),
 }
 
 #[derive(clap::Args)] // Changed Parser to Args for subcommand structs
@@ -46,6 +56,12 @@ struct StartArgs {
     /// For example, to enable verbose logging: --extra-args "--log=trace"
     #[arg(long)]
     extra_args: Vec<String>,
+
+    #[command(flatten)]
+    version: VersionArgs,
+
+    #[command(flatten)]
+    download: DownloadOptions,
 }
 
 #[derive(clap::Args)] // Changed Parser to Args for subcommand structs
@@ -56,6 +72,170 @@ struct DownloadArgs {
     /// Can also be set using the VSCODE_SERVER_DIR environment variable.
     #[arg(long, env = "VSCODE_SERVER_DIR")]
     server_dir: Option<PathBuf>,
+
+    /// Downloads the server for this target triple (e.g. "x86_64-pc-windows-msvc") instead of the host.
+    /// Repeat to download servers for several targets.
+    #[arg(long = "target", value_name = "TRIPLE", value_parser = Platform::from_target_triple)]
+    targets: Vec<Platform>,
+
+    #[command(flatten)]
+    version: VersionArgs,
+
+    #[command(flatten)]
+    download: DownloadOptions,
+}
+
+#[derive(clap::Args)]
+/// Arguments for the `install` subcommand.
+struct InstallArgs {
+    /// The server archive to install, as downloaded from the VSCode update service.
+    #[arg(long)]
+    archive: PathBuf,
+
+    /// The VSCode commit the archive must contain, as a full 40-character SHA.
+    /// Read from the archive's product.json if not given.
+    #[arg(long)]
+    commit: Option<String>,
+
+    /// Specifies the directory where the VSCode server will be installed.
+    /// If not provided, a default directory will be used (see ServerConfig::default()).
+    /// Can also be set using the VSCODE_SERVER_DIR environment variable.
+    #[arg(long, env = "VSCODE_SERVER_DIR")]
+    server_dir: Option<PathBuf>,
+
+    /// The expected SHA-256 hash (hex) of the archive.
+    #[arg(long)]
+    sha256: Option<String>,
+}
+
+#[derive(clap::Args)]
+/// Arguments for the `list` subcommand.
+struct ListArgs {
+    /// Specifies the directory the VSCode servers are installed in.
+    /// If not provided, a default directory will be used (see ServerConfig::default()).
+    /// Can also be set using the VSCODE_SERVER_DIR environment variable.
+    #[arg(long, env = "VSCODE_SERVER_DIR")]
+    server_dir: Option<PathBuf>,
+
+    /// Prints the installed servers as JSON instead of a table.
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(clap::Args)]
+/// Arguments controlling which server version is resolved, shared by the subcommands.
+struct VersionArgs {
+    /// Pins the monaco-vscode-api version the server must match.
+    /// Accepts an exact version (e.g. "16.1.1") or a semver range (e.g. "^16.0").
+    /// Can also be set using the MONACO_VSCODE_API_VERSION environment variable.
+    #[arg(long, env = "MONACO_VSCODE_API_VERSION")]
+    monaco_api_version: Option<String>,
+
+    /// Matches the server to the monaco-vscode-api version used by a frontend project.
+    /// Reads node_modules, package-lock.json, pnpm-lock.yaml, yarn.lock or package.json in this directory.
+    /// Can also be set using the MONACO_VSCODE_FRONTEND_DIR environment variable.
+    #[arg(long, env = "MONACO_VSCODE_FRONTEND_DIR")]
+    frontend_dir: Option<PathBuf>,
+
+    /// Allows pre-release monaco-vscode-api versions (e.g. "17.0.0-next.1") to be selected.
+    #[arg(long)]
+    allow_prerelease: bool,
+
+    /// Resolves the server from the local cache without any network access.
+    /// Uses the last resolved version if still installed, otherwise the newest installed server.
+    /// Can also be enabled by setting the VSCODE_SERVER_OFFLINE environment variable to 1, true or yes.
+    #[arg(long)]
+    offline: bool,
+
+    /// Ignores the cached version resolution and queries monaco-vscode-api again.
+    #[arg(long)]
+    refresh: bool,
+
+    /// Selects the VSCode release quality of the server: stable, insider or exploration.
+    /// Can also be set using the VSCODE_SERVER_QUALITY environment variable.
+    #[arg(long, env = "VSCODE_SERVER_QUALITY")]
+    quality: Option<Quality>,
+}
+
+impl VersionArgs {
+    /// Applies the version selection arguments to a `ServerConfig`.
+    fn apply(self, config: &mut ServerConfig) {
+        config.monaco_api_version = self.monaco_api_version;
+        config.frontend_dir = self.frontend_dir;
+        config.allow_prerelease = self.allow_prerelease;
+        config.offline |= self.offline;
+        config.force_refresh = self.refresh;
+        if let Some(quality) = self.quality {
+            config.quality = quality;
+        }
+    }
+}
+
+#[derive(clap::Args)]
+/// Arguments controlling how the server is downloaded, shared by the subcommands.
+struct DownloadOptions {
+    /// Sends all requests through this proxy, e.g. "http://proxy.example.com:8080".
+    /// Without it, the HTTPS_PROXY, HTTP_PROXY and ALL_PROXY environment variables are used.
+    #[arg(long)]
+    proxy: Option<String>,
+
+    /// The user name to authenticate with the proxy.
+    #[arg(long)]
+    proxy_user: Option<String>,
+
+    /// The password to authenticate with the proxy.
+    /// Can also be set using the VSCODE_SERVER_PROXY_PASSWORD environment variable.
+    #[arg(long, env = "VSCODE_SERVER_PROXY_PASSWORD", hide_env_values = true)]
+    proxy_password: Option<String>,
+
+    /// Comma-separated hosts and domains that bypass the proxy.
+    /// Without it, the NO_PROXY environment variable is used.
+    #[arg(long, value_delimiter = ',')]
+    no_proxy: Vec<String>,
+
+    /// A PEM file with additional root certificates to trust.
+    /// Can also be set using the VSCODE_SERVER_CA_BUNDLE environment variable.
+    #[arg(long, env = "VSCODE_SERVER_CA_BUNDLE")]
+    ca_bundle: Option<PathBuf>,
+
+    /// A mirror URL template to download the server from before the official update service.
+    /// Supports the {commit}, {flavor} and {quality} placeholders; repeat to try several mirrors in order.
+    /// Can also be set as a comma-separated list using the VSCODE_SERVER_MIRRORS environment variable.
+    #[arg(long = "mirror", env = "VSCODE_SERVER_MIRRORS", value_delimiter = ',')]
+    mirrors: Vec<String>,
+
+    /// Unpacks the server while it downloads instead of saving the archive first.
+    /// Halves the disk space needed, but an interrupted download restarts from the beginning.
+    #[arg(long)]
+    streaming_extraction: bool,
+
+    /// How long to wait for a connection, in seconds (default: 30).
+    #[arg(long)]
+    connect_timeout: Option<u64>,
+
+    /// How long to wait for data on an open connection, in seconds (default: 300).
+    #[arg(long)]
+    read_timeout: Option<u64>,
+}
+
+impl DownloadOptions {
+    /// Applies the download arguments to a `ServerConfig`.
+    fn apply(self, config: &mut ServerConfig) {
+        let network = &mut config.network;
+        network.proxy = self.proxy;
+        network.proxy_username = self.proxy_user;
+        network.proxy_password = self.proxy_password;
+        network.no_proxy = self.no_proxy;
+        network.ca_bundle = self.ca_bundle;
+        if let Some(secs) = self.connect_timeout {
+            network.connect_timeout_secs = secs;
+        }
+        if let Some(secs) = self.read_timeout {
+            network.read_timeout_secs = secs;
+        }
+        config.mirrors = self.mirrors;
+        config.streaming_extraction = self.streaming_extraction;
+    }
 }
 
 #[tokio::main]
@@ -75,9 +255,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 config.server_dir = server_dir;
             }
             config.args.extend(args.extra_args);
+            args.version.apply(&mut config);
+            args.download.apply(&mut config);
 
             println!("Starting server with config: {:?}", config);
             let mut manager = VscodeServerManager::with_config(config).await?;
+            manager.set_event_sink(EventSink::new(print_event));
             
             if let Err(e) = manager.ensure_server().await {
                 eprintln!("Error ensuring server is available: {}", e);
@@ -85,6 +268,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 return Err(Box::new(e) as Box<dyn std::error::Error>);
             }
             println!("VSCode server files are ready.");
+            print_server_info(&manager);
 
             if let Err(e) = manager.start().await {
                 eprintln!("Error starting server: {}", e);
@@ -104,17 +288,209 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             if let Some(server_dir) = args.server_dir {
                 config.server_dir = server_dir;
             }
+            args.version.apply(&mut config);
+            args.download.apply(&mut config);
 
             println!("Ensuring server is downloaded to: {:?}", config.server_dir);
             let mut manager = VscodeServerManager::with_config(config).await?;
-            if let Err(e) = manager.ensure_server().await {
-                eprintln!("Error ensuring server is available: {}", e);
-                 eprintln!("If this is a download or extraction error, please check your network connection and permissions.");
-                return Err(Box::new(e) as Box<dyn std::error::Error>);
+            manager.set_event_sink(EventSink::new(print_event));
+            if args.targets.is_empty() {
+                if let Err(e) = manager.ensure_server().await {
+                    eprintln!("Error ensuring server is available: {}", e);
+                    eprintln!("If this is a download or extraction error, please check your network connection and permissions.");
+                    return Err(Box::new(e) as Box<dyn std::error::Error>);
+                }
+                println!("VSCode server download/extraction complete.");
+                print_server_info(&manager);
+            }
+            match manager.ensure_servers_for(&args.targets).await {
+                Ok(installed) => {
+                    for info in installed {
+                        println!("{}", describe_server(&info));
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Error ensuring servers for the requested targets are available: {}", e);
+                    return Err(Box::new(e) as Box<dyn std::error::Error>);
+                }
+            }
+        }
+        Commands::Install(args) => {
+            let mut config = ServerConfig::default();
+            if let Some(server_dir) = args.server_dir {
+                config.server_dir = server_dir;
+            }
+            config.expected_sha256 = args.sha256;
+
+            let mut manager = VscodeServerManager::with_config(config).await?;
+            manager.set_event_sink(EventSink::new(print_event));
+            match manager.install_from_archive(&args.archive, args.commit.as_deref()).await {
+                Ok(info) => println!("Installed {}", describe_server(&info)),
+                Err(e) => {
+                    eprintln!("Error installing server from {}: {}", args.archive.display(), e);
+                    return Err(Box::new(e) as Box<dyn std::error::Error>);
+                }
+            }
+        }
+        Commands::List(args) => {
+            let mut config = ServerConfig::default();
+            if let Some(server_dir) = args.server_dir {
+                config.server_dir = server_dir;
+            }
+
+            let manager = VscodeServerManager::with_config(config).await?;
+            let installed = manager.installed_versions().await?;
+            if args.json {
+                println!("{}", serde_json::to_string_pretty(&installed)?);
+            } else if installed.is_empty() {
+                println!("No VSCode servers installed in {}", manager.config().server_dir.display());
+            } else {
+                print_installed(&installed);
             }
-            println!("VSCode server download/extraction complete.");
         }
     }
 
     Ok(())
 }
+
+/// Renders a progress event reported while the server is prepared.
+fn print_event(event: ServerEvent) {
+    match event {
+        ServerEvent::FrontendVersionDetected { version, source } => {
+            println!("Using monaco-vscode-api {} from {}", version, source.display());
+        }
+        ServerEvent::Resolved { info } => {
+            if matches!(info.source, ResolutionSource::LastResolved | ResolutionSource::Installed) {
+                println!("Using locally cached VSCode server {} ({:?})", info.vscode_commit, info.source);
+            }
+        }
+        ServerEvent::IncompleteInstallRemoved { paths } => {
+            for path in paths {
+                println!("Removed leftovers of an interrupted install: {}", path.display());
+            }
+        }
+        ServerEvent::WaitingForLock { path } => {
+            println!("Waiting for another process to finish installing the server ({})", path.display());
+        }
+        ServerEvent::DownloadStarted { url, resumed_from, .. } => {
+            println!("Downloading VSCode server...");
+            println!("URL: {}", url);
+            if resumed_from > 0 {
+                println!("Resuming download at byte {}", resumed_from);
+            }
+        }
+        ServerEvent::DownloadProgress { downloaded, total_bytes: Some(total) } if total > 0 => {
+            print!("\rDownloading: {:.1}%", downloaded as f64 / total as f64 * 100.0);
+            let _ = std::io::stdout().flush();
+        }
+        ServerEvent::MirrorFailed { url, reason } => {
+            eprintln!("\nDownload from {} failed ({}), trying the next mirror", url, reason);
+        }
+        ServerEvent::DownloadFinished { .. } => println!("\nDownload complete!"),
+        ServerEvent::ExtractionStarted { .. } => println!("Extracting server..."),
+        ServerEvent::ExtractionProgress { entries, total_entries, .. } => {
+            match total_entries {
+                Some(total) if total > 0 => print!("\rExtracting: {:.1}%", entries as f64 / total as f64 * 100.0),
+                _ => print!("\rExtracted {} files", entries),
+            }
+            let _ = std::io::stdout().flush();
+        }
+        ServerEvent::ExtractionFinished { .. } => println!("\nExtraction complete!"),
+        ServerEvent::LegacyInstallAdopted { from, path } => {
+            println!("Adopted existing install {} as {}", from.display(), path.display());
+        }
+        ServerEvent::Installed { path } => println!("Server ready at: {}", path.display()),
+        ServerEvent::RetryScheduled { url, attempt, max_attempts, delay, reason } => {
+            eprintln!(
+                "Request to {} failed ({}), retrying in {:.1}s (attempt {}/{})",
+                url, reason, delay.as_secs_f64(), attempt + 1, max_attempts
+            );
+        }
+        ServerEvent::Warning(message) => eprintln!("Warning: {}", message),
+        _ => {}
+    }
+}
+
+/// Prints the installed servers as a table, marking the selected one with an asterisk.
+fn print_installed(installed: &[InstalledServer]) {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let header = ["", "COMMIT", "VSCODE", "MONACO-VSCODE-API", "PLATFORM", "QUALITY", "INSTALLED", "LAST USED", "SIZE"]
+        .map(String::from);
+    let rows: Vec<[String; 9]> = installed.iter()
+        .map(|server| [
+            if server.selected { "*" } else { "" }.to_string(),
+            server.vscode_commit.chars().take(12).collect(),
+            server.vscode_version.clone().unwrap_or_else(|| "-".to_string()),
+            server.monaco_api_version.clone(),
+            server.platform.as_str().to_string(),
+            server.quality.to_string(),
+            format_age(Some(server.installed_at), now),
+            format_age(server.last_used_at, now),
+            format_size(server.size_bytes),
+        ])
+        .collect();
+
+    let mut widths = [0; 9];
+    for row in std::iter::once(&header).chain(&rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    for row in std::iter::once(&header).chain(&rows) {
+        let line: Vec<String> = row.iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    }
+}
+
+/// Formats a Unix timestamp as the time elapsed until `now`, e.g. "3 days ago".
+fn format_age(timestamp: Option<u64>, now: u64) -> String {
+    let Some(timestamp) = timestamp.filter(|t| *t > 0) else {
+        return "never".to_string();
+    };
+    let elapsed = now.saturating_sub(timestamp);
+    let (count, unit) = match elapsed {
+        0..60 => return "just now".to_string(),
+        60..3_600 => (elapsed / 60, "minute"),
+        3_600..86_400 => (elapsed / 3_600, "hour"),
+        _ => (elapsed / 86_400, "day"),
+    };
+    format!("{} {}{} ago", count, unit, if count == 1 { "" } else { "s" })
+}
+
+/// Formats a size in bytes with a binary unit, e.g. "195.3 MiB".
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Prints the resolved server version, naming the VSCode release when it is known.
+fn print_server_info(manager: &VscodeServerManager) {
+    if let Some(info) = manager.info() {
+        println!("{}", describe_server(info));
+    }
+}
+
+/// Formats a one-line description such as "VSCode 1.98.2 (release/1.98, commit abc...) for monaco-vscode-api v16.1.1".
+fn describe_server(info: &ServerInfo) -> String {
+    let release = match (&info.vscode_version, &info.vscode_ref) {
+        (Some(version), Some(git_ref)) => format!("VSCode {} ({}, commit {})", version, git_ref, info.vscode_commit),
+        (Some(version), None) => format!("VSCode {} (commit {})", version, info.vscode_commit),
+        (None, _) => format!("VSCode commit {}", info.vscode_commit),
+    };
+    format!("{} for monaco-vscode-api {} [{}, {}]", release, info.monaco_api_version, info.platform, info.quality)
+}
@@ -1,6 +1,7 @@
 // platform.rs - Platform detection and support
 
 use std::fmt;
+use std::str::FromStr;
 use serde::{Deserialize, Serialize};
 
 /// Supported platforms for VSCode server
@@ -46,6 +47,18 @@ impl Platform {
         ));
     }
     
+    /// Gets the short name of this platform, e.g. `linux-x64`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Platform::LinuxX64 => "linux-x64",
+            Platform::LinuxArm64 => "linux-arm64",
+            Platform::LinuxArmhf => "linux-armhf",
+            Platform::DarwinX64 => "darwin-x64",
+            Platform::DarwinArm64 => "darwin-arm64",
+            Platform::Win32X64 => "win32-x64",
+        }
+    }
+    
     /// Gets the VSCode server flavor string for this platform
     pub fn server_flavor(&self) -> &'static str {
         match self {
@@ -58,11 +71,11 @@ impl Platform {
         }
     }
     
-    /// Gets the URL suffix for downloading
-    pub fn url_suffix(&self) -> &'static str {
-        match self {
-            Platform::Win32X64 => "archive",
-            _ => "stable",
+    /// Gets the URL suffix (the quality segment) for downloading
+    pub fn url_suffix(&self, quality: Quality) -> &'static str {
+        match (self, quality) {
+            (Platform::Win32X64, Quality::Stable) => "archive",
+            _ => quality.as_str(),
         }
     }
     
@@ -89,4 +102,61 @@ impl fmt::Display for Platform {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.server_flavor())
     }
-}
\ No newline at end of file
+}
+
+impl FromStr for Platform {
+    type Err = String;
+
+    /// Parses a platform name as returned by `as_str`, e.g. `linux-x64`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "linux-x64" => Ok(Platform::LinuxX64),
+            "linux-arm64" => Ok(Platform::LinuxArm64),
+            "linux-armhf" => Ok(Platform::LinuxArmhf),
+            "darwin-x64" => Ok(Platform::DarwinX64),
+            "darwin-arm64" => Ok(Platform::DarwinArm64),
+            "win32-x64" => Ok(Platform::Win32X64),
+            _ => Err(format!("Unknown platform: {}", s)),
+        }
+    }
+}
+
+/// VSCode release quality (update channel) of the server
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Quality {
+    #[default]
+    Stable,
+    Insider,
+    Exploration,
+}
+
+impl Quality {
+    /// Gets the quality name used by the VSCode update service
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Quality::Stable => "stable",
+            Quality::Insider => "insider",
+            Quality::Exploration => "exploration",
+        }
+    }
+}
+
+impl fmt::Display for Quality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Quality {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "stable" => Ok(Quality::Stable),
+            "insider" | "insiders" => Ok(Quality::Insider),
+            "exploration" => Ok(Quality::Exploration),
+            _ => Err(format!("Unknown quality: {} (expected stable, insider or exploration)", s)),
+        }
+    }
+}
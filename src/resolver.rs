@@ -0,0 +1,566 @@
+// resolver.rs - Version resolution for VSCode server
+
+use crate::cache::{unix_now, ResolutionSource};
+use crate::events::EventSink;
+use crate::frontend;
+use crate::http::HttpClient;
+use crate::{Platform, Quality, ServerConfig, ServerError, ServerInfo};
+use futures_util::future::BoxFuture;
+use reqwest::StatusCode;
+use semver::{Op, Prerelease, Version, VersionReq};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// The hosts the `GITHUB_TOKEN` environment variable is sent to
+const GITHUB_HOSTS: [&str; 2] = ["api.github.com", "raw.githubusercontent.com"];
+
+/// Resolves which VSCode server build to use.
+///
+/// Implementations decide which `monaco-vscode-api` release (and therefore which VSCode
+/// commit) the server must match, and where the server for a platform can be downloaded.
+/// The default implementation is `GitHubResolver`; custom implementations can be installed
+/// with `VscodeServerManager::set_resolver`, e.g. to resolve from an internal service or a
+/// stub in tests.
+pub trait VersionResolver: Send + Sync {
+    /// Resolves the server for `platform` according to `config`, reporting progress to `events`.
+    fn resolve<'a>(
+        &'a self,
+        config: &'a ServerConfig,
+        platform: Platform,
+        events: &'a EventSink,
+    ) -> BoxFuture<'a, Result<ServerInfo, ServerError>>;
+}
+
+/// Base URLs used to resolve and download the VSCode server.
+///
+/// The defaults point at the public GitHub repository of `CodinGame/monaco-vscode-api`
+/// and the VSCode update service; they can be changed to use an internal mirror, a fork
+/// of `monaco-vscode-api`, or a local stub server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Endpoints {
+        /// The GitHub API URL of the `monaco-vscode-api` repository, used to list its tags.
+    pub github_api_url: String,
+        /// The base URL serving raw files of the `monaco-vscode-api` repository by tag.
+    pub raw_content_url: String,
+        /// The URL template of the server archive.
+    ///
+    /// `{commit}`, `{flavor}` (e.g. `server-linux-x64`) and `{quality}` (e.g. `stable`)
+    /// are replaced with the values for the resolved server. As the update service expects,
+    /// `{quality}` is `archive` for stable Windows servers in this template only.
+    pub download_url_template: String,
+        /// The URL template of the update service's metadata for a server archive, whose
+    /// `sha256hash` field is used to verify downloads. Uses the same placeholders as
+    /// `download_url_template`; `None` disables the lookup.
+    #[serde(default)]
+    pub checksum_url_template: Option<String>,
+}
+
+/// Provides default settings for `Endpoints`.
+/// - `github_api_url`: `https://api.github.com/repos/CodinGame/monaco-vscode-api`
+/// - `raw_content_url`: `https://raw.githubusercontent.com/CodinGame/monaco-vscode-api`
+/// - `download_url_template`: `https://update.code.visualstudio.com/commit:{commit}/{flavor}/{quality}`
+/// - `checksum_url_template`: `https://update.code.visualstudio.com/api/versions/commit:{commit}/{flavor}/{quality}`
+impl Default for Endpoints {
+    fn default() -> Self {
+        Self {
+            github_api_url: "https://api.github.com/repos/CodinGame/monaco-vscode-api".to_string(),
+            raw_content_url: "https://raw.githubusercontent.com/CodinGame/monaco-vscode-api".to_string(),
+            download_url_template: "https://update.code.visualstudio.com/commit:{commit}/{flavor}/{quality}".to_string(),
+            checksum_url_template: Some(
+                "https://update.code.visualstudio.com/api/versions/commit:{commit}/{flavor}/{quality}".to_string(),
+            ),
+        }
+    }
+}
+
+impl Endpoints {
+    /// Builds the server download URL for a VSCode commit, platform and quality from `download_url_template`.
+    ///
+    /// `{quality}` is expanded to `Platform::url_suffix`, e.g. `archive` for stable Windows servers.
+    pub fn download_url(&self, vscode_commit: &str, platform: Platform, quality: Quality) -> String {
+        expand_template(&self.download_url_template, vscode_commit, platform, platform.url_suffix(quality))
+    }
+    
+    /// Replaces the `{commit}`, `{flavor}` and `{quality}` placeholders of a URL template,
+    /// such as a mirror or `checksum_url_template`. `{quality}` is expanded to `Quality::as_str`.
+    pub fn expand(&self, template: &str, vscode_commit: &str, platform: Platform, quality: Quality) -> String {
+        expand_template(template, vscode_commit, platform, quality.as_str())
+    }
+}
+
+/// Replaces the placeholders of a URL template, using `quality` for `{quality}`
+fn expand_template(template: &str, vscode_commit: &str, platform: Platform, quality: &str) -> String {
+    template
+        .replace("{commit}", vscode_commit)
+        .replace("{flavor}", platform.server_flavor())
+        .replace("{quality}", quality)
+}
+
+/// The default `VersionResolver`, backed by the `monaco-vscode-api` GitHub repository.
+///
+/// If `ServerConfig::frontend_dir` is set, the version is read from the frontend project;
+/// otherwise the repository tags are resolved against `ServerConfig::monaco_api_version`.
+/// The VSCode commit is then read from the selected tag's `package.json`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GitHubResolver;
+
+impl VersionResolver for GitHubResolver {
+    fn resolve<'a>(
+        &'a self,
+        config: &'a ServerConfig,
+        platform: Platform,
+        events: &'a EventSink,
+    ) -> BoxFuture<'a, Result<ServerInfo, ServerError>> {
+        Box::pin(async move {
+            let http = HttpClient::new(config, events)?;
+            match &config.frontend_dir {
+                Some(frontend_dir) => frontend::detect_version(&http, config, frontend_dir, platform).await,
+                None => detect_version(&http, config, config.monaco_api_version.as_deref(), platform).await,
+            }
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[allow(dead_code)] // Fields are for deserialization structure, not all are directly used
+struct GitHubTag {
+    name: String,
+    commit: CommitInfo,
+}
+
+#[derive(Deserialize)]
+#[allow(dead_code)] // Fields are for deserialization structure, not all are directly used
+struct CommitInfo {
+    sha: String,
+}
+
+/// The parts of monaco-vscode-api's `package.json` used for version detection
+#[derive(Deserialize)]
+pub(crate) struct PackageJson {
+    #[serde(default)]
+    pub version: String,
+    pub config: ConfigSection,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ConfigSection {
+    pub vscode: VscodeConfig,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct VscodeConfig {
+    pub commit: String,
+    /// The VSCode release version, e.g. `1.98.2`
+    #[serde(default)]
+    pub version: Option<String>,
+    /// The VSCode git ref the commit was taken from
+    #[serde(default, rename = "ref")]
+    pub git_ref: Option<String>,
+}
+
+/// Number of tags requested per page from the GitHub tags API (the API maximum)
+const TAGS_PER_PAGE: usize = 100;
+
+/// Detects the compatible VSCode server version information.
+///
+/// This function pages through all tags of the `monaco-vscode-api` repository
+/// and parses their names as semver versions, ignoring tags that are not valid versions.
+/// The highest version satisfying `requested_version` (or simply the highest version when
+/// `None`) is selected; pre-releases are only considered when `config.allow_prerelease` is
+/// `true` or the requested range itself names a pre-release.
+/// It then fetches the `package.json` for that tag to extract the
+/// corresponding VSCode commit SHA (`vscodeRef`).
+///
+/// Finally, it constructs a `ServerInfo` struct containing the `monaco-vscode-api` version,
+/// the VSCode commit SHA, version and ref, the platform, and the direct download URL for the server.
+///
+/// # Arguments
+///
+/// * `http` - The client used for requests, retrying them according to `config.retry`.
+/// * `config` - The configuration providing the endpoints and pre-release policy.
+/// * `requested_version` - An optional exact `monaco-vscode-api` version (e.g. `16.1.1`)
+///   or semver range (e.g. `^16.0`) to resolve instead of the latest release.
+/// * `platform` - The platform the server is resolved for.
+///
+/// # Errors
+///
+/// Returns `ServerError` if:
+/// - Network requests fail (`ServerError::Network`).
+/// - GitHub's rate limit is exceeded (`ServerError::RateLimited`).
+/// - No release tags are found for `monaco-vscode-api`, the requested version is not a valid
+///   version or range, or no tag matches it (`ServerError::VersionDetectionFailed`).
+/// - `package.json` cannot be fetched or parsed (`ServerError::Network`, `ServerError::VersionDetectionFailed`).
+pub(crate) async fn detect_version(
+    http: &HttpClient,
+    config: &ServerConfig,
+    requested_version: Option<&str>,
+    platform: Platform,
+) -> Result<ServerInfo, ServerError> {
+    let req = requested_version.map(parse_version_req).transpose()?;
+    let tags = fetch_tags(http, config).await?;
+
+    let selected_tag = select_tag(&tags, req.as_ref(), config.allow_prerelease)
+        .ok_or_else(|| ServerError::VersionDetectionFailed(match requested_version {
+            Some(requested) => format!(
+                "No monaco-vscode-api tag matches the requested version '{}'",
+                requested
+            ),
+            None => "No release tags found in monaco-vscode-api repository".to_string(),
+        }))?;
+
+    detect_version_for_tag(http, config, &selected_tag.name, platform).await
+}
+
+/// Fetches every tag of the monaco-vscode-api repository, following pagination
+async fn fetch_tags(http: &HttpClient, config: &ServerConfig) -> Result<Vec<GitHubTag>, ServerError> {
+    let mut tags = Vec::new();
+
+    for page in 1.. {
+        let url = format!(
+            "{}/tags?per_page={}&page={}",
+            config.endpoints.github_api_url.trim_end_matches('/'),
+            TAGS_PER_PAGE,
+            page
+        );
+        let page_tags: Vec<GitHubTag> = github_get(http, config, &url).await?;
+
+        let last_page = page_tags.len() < TAGS_PER_PAGE;
+        tags.extend(page_tags);
+        if last_page {
+            break;
+        }
+    }
+
+    Ok(tags)
+}
+
+/// Detects the VSCode server version information for a known `monaco-vscode-api` tag.
+///
+/// Unlike `detect_version`, this skips the GitHub tags API and fetches the tag's
+/// `package.json` directly.
+///
+/// # Errors
+///
+/// Returns `ServerError` if `package.json` cannot be fetched or parsed (`ServerError::Network`,
+/// `ServerError::VersionDetectionFailed`) or GitHub's rate limit is exceeded (`ServerError::RateLimited`).
+pub(crate) async fn detect_version_for_tag(
+    http: &HttpClient,
+    config: &ServerConfig,
+    tag: &str,
+    platform: Platform,
+) -> Result<ServerInfo, ServerError> {
+    // Get VSCode commit from package.json
+    let url = format!(
+        "{}/{}/package.json",
+        config.endpoints.raw_content_url.trim_end_matches('/'),
+        tag
+    );
+    let package_json: PackageJson = github_get(http, config, &url).await?;
+
+    server_info(config, tag.to_string(), package_json.config.vscode, platform)
+}
+
+/// Sends an (optionally authenticated) GET request to GitHub and decodes the JSON response.
+///
+/// The token is taken from `ServerConfig::github_token`, falling back to the `GITHUB_TOKEN`
+/// environment variable for GitHub's own hosts. Rate-limit rejections are reported as `ServerError::RateLimited`
+/// and any other unsuccessful status as `ServerError::VersionDetectionFailed`.
+async fn github_get<T: DeserializeOwned>(
+    http: &HttpClient,
+    config: &ServerConfig,
+    url: &str,
+) -> Result<T, ServerError> {
+    let token = github_token(config, url);
+    let response = http.send(|client| match &token {
+        Some(token) => client.get(url).bearer_auth(token),
+        None => client.get(url),
+    }).await?;
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response.json().await?);
+    }
+
+    if status == StatusCode::FORBIDDEN || status == StatusCode::TOO_MANY_REQUESTS {
+        let header = |name: &str| -> Option<u64> {
+            response.headers().get(name)?.to_str().ok()?.trim().parse().ok()
+        };
+        let retry_after = header("retry-after");
+        if retry_after.is_some() || header("x-ratelimit-remaining") == Some(0) {
+            let reset_at = retry_after
+                .map(|secs| unix_now() + secs)
+                .or_else(|| header("x-ratelimit-reset"));
+            return Err(ServerError::RateLimited { reset_at });
+        }
+    }
+
+    Err(ServerError::VersionDetectionFailed(format!("Request to {} failed: {}", url, status)))
+}
+
+/// Returns the token to send with a request to `url`: the one from the config, or the
+/// `GITHUB_TOKEN` environment variable if `url` points to one of `GITHUB_HOSTS`
+fn github_token(config: &ServerConfig, url: &str) -> Option<String> {
+    config.github_token.clone()
+        .or_else(|| is_github_url(url).then(|| std::env::var("GITHUB_TOKEN").ok()).flatten())
+        .filter(|token| !token.trim().is_empty())
+}
+
+/// Checks whether `url` is an HTTPS URL on one of `GITHUB_HOSTS`
+fn is_github_url(url: &str) -> bool {
+    reqwest::Url::parse(url)
+        .is_ok_and(|url| url.scheme() == "https" && url.host_str().is_some_and(|host| GITHUB_HOSTS.contains(&host)))
+}
+
+/// Builds the `ServerInfo` for a VSCode release on the given platform, rejecting a release whose
+/// commit is not a full commit SHA
+pub(crate) fn server_info(
+    config: &ServerConfig,
+    monaco_api_version: String,
+    vscode: VscodeConfig,
+    platform: Platform,
+) -> Result<ServerInfo, ServerError> {
+    if !is_commit_sha(&vscode.commit) {
+        return Err(ServerError::VersionDetectionFailed(format!(
+            "monaco-vscode-api {} names an invalid VSCode commit {:?}", monaco_api_version, vscode.commit
+        )));
+    }
+    let download_url = config.endpoints.download_url(&vscode.commit, platform, config.quality);
+
+    Ok(ServerInfo {
+        monaco_api_version,
+        vscode_commit: vscode.commit,
+        vscode_version: vscode.version,
+        vscode_ref: vscode.git_ref,
+        platform,
+        quality: config.quality,
+        download_url,
+        source: ResolutionSource::Resolver,
+        resolved_at: 0,
+        downloaded_from: None,
+    })
+}
+
+/// Parses a requested `monaco-vscode-api` version into a semver requirement.
+///
+/// A bare version such as `16.1.1` or `v16.1.1` is treated as an exact pin (`=16.1.1`),
+/// anything else is parsed as a semver range (e.g. `^16.0`, `>=15, <17`).
+pub(crate) fn parse_version_req(requested: &str) -> Result<VersionReq, ServerError> {
+    let trimmed = requested.trim();
+    let bare = trimmed.strip_prefix('v').unwrap_or(trimmed);
+
+    if let Ok(version) = Version::parse(bare) {
+        return VersionReq::parse(&format!("={}", version))
+            .map_err(|e| ServerError::VersionDetectionFailed(e.to_string()));
+    }
+
+    VersionReq::parse(trimmed).map_err(|e| ServerError::VersionDetectionFailed(format!(
+        "Invalid monaco-vscode-api version '{}': {}",
+        requested, e
+    )))
+}
+
+/// Parses a git tag name such as `v16.1.1` into a semver version
+pub(crate) fn parse_tag_version(name: &str) -> Option<Version> {
+    Version::parse(name.strip_prefix('v').unwrap_or(name)).ok()
+}
+
+/// Selects the tag with the highest semver version, optionally restricted to `req`.
+///
+/// Tags whose names are not valid versions (e.g. `next`) are ignored, as are pre-releases
+/// unless `allow_prerelease` is set or `req` explicitly names a pre-release. Ties between
+/// tags parsing to the same version (e.g. `v1.0.0` and `1.0.0`) are broken by name so the
+/// result does not depend on the order returned by the API.
+fn select_tag<'a>(tags: &'a [GitHubTag], req: Option<&VersionReq>, allow_prerelease: bool) -> Option<&'a GitHubTag> {
+    let names_prerelease = req.is_some_and(|req| req.comparators.iter().any(|c| !c.pre.is_empty()));
+
+    tags.iter()
+        .filter_map(|tag| parse_tag_version(&tag.name).map(|version| (version, tag)))
+        .filter(|(version, _)| allow_prerelease || names_prerelease || version.pre.is_empty())
+        .filter(|(version, _)| req.is_none_or(|req| matches_req(req, version, allow_prerelease)))
+        .max_by(|(a, a_tag), (b, b_tag)| a.cmp(b).then_with(|| a_tag.name.cmp(&b_tag.name)))
+        .map(|(_, tag)| tag)
+}
+
+/// Checks whether `version` satisfies `req`.
+///
+/// `VersionReq::matches` only accepts a pre-release if a comparator names a pre-release of the
+/// same major.minor.patch, so with `allow_prerelease` a pre-release of a range such as `^16.0`
+/// is matched by its release version instead. Exact pins still only match exactly.
+pub(crate) fn matches_req(req: &VersionReq, version: &Version, allow_prerelease: bool) -> bool {
+    if req.matches(version) {
+        return true;
+    }
+    if !allow_prerelease || version.pre.is_empty() || is_exact_pin(req) {
+        return false;
+    }
+    let release = Version { pre: Prerelease::EMPTY, ..version.clone() };
+    req.matches(&release)
+}
+
+/// Checks whether `req` pins a single version, such as `=16.1.1`
+pub(crate) fn is_exact_pin(req: &VersionReq) -> bool {
+    req.comparators.iter().any(|c| c.op == Op::Exact && c.patch.is_some())
+}
+
+/// Checks whether `commit` is a full VSCode commit SHA: exactly 40 hexadecimal digits.
+///
+/// Commits name install directories, so anything else must be rejected before building paths.
+pub(crate) fn is_commit_sha(commit: &str) -> bool {
+    commit.len() == 40 && commit.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{serve, Reply};
+
+    /// A page of the GitHub tags API as recorded for monaco-vscode-api, in API order
+    const RECORDED_TAGS: &str = r#"[
+        { "name": "v9.0.3", "commit": { "sha": "a1", "url": "https://api.github.com/repos/CodinGame/monaco-vscode-api/commits/a1" } },
+        { "name": "v16.1.1", "commit": { "sha": "a2", "url": "https://api.github.com/repos/CodinGame/monaco-vscode-api/commits/a2" } },
+        { "name": "next", "commit": { "sha": "a3", "url": "https://api.github.com/repos/CodinGame/monaco-vscode-api/commits/a3" } },
+        { "name": "v16.2.0-next.1", "commit": { "sha": "a4", "url": "https://api.github.com/repos/CodinGame/monaco-vscode-api/commits/a4" } },
+        { "name": "v10.1.0", "commit": { "sha": "a5", "url": "https://api.github.com/repos/CodinGame/monaco-vscode-api/commits/a5" } },
+        { "name": "16.1.1", "commit": { "sha": "a6", "url": "https://api.github.com/repos/CodinGame/monaco-vscode-api/commits/a6" } },
+        { "name": "v16.0.0", "commit": { "sha": "a7", "url": "https://api.github.com/repos/CodinGame/monaco-vscode-api/commits/a7" } },
+        { "name": "v17.0.0-next.2", "commit": { "sha": "a8", "url": "https://api.github.com/repos/CodinGame/monaco-vscode-api/commits/a8" } },
+        { "name": "next-17", "commit": { "sha": "a9", "url": "https://api.github.com/repos/CodinGame/monaco-vscode-api/commits/a9" } },
+        { "name": "v15.0.2", "commit": { "sha": "b1", "url": "https://api.github.com/repos/CodinGame/monaco-vscode-api/commits/b1" } }
+    ]"#;
+
+    fn recorded_tags() -> Vec<GitHubTag> {
+        serde_json::from_str(RECORDED_TAGS).unwrap()
+    }
+
+    fn select(tags: &[GitHubTag], requested: Option<&str>, allow_prerelease: bool) -> Option<String> {
+        let req = requested.map(|requested| parse_version_req(requested).unwrap());
+        select_tag(tags, req.as_ref(), allow_prerelease).map(|tag| tag.name.clone())
+    }
+
+    #[test]
+    fn selects_highest_release_regardless_of_order() {
+        let mut tags = recorded_tags();
+        assert_eq!(select(&tags, None, false).as_deref(), Some("v16.1.1"));
+        tags.reverse();
+        assert_eq!(select(&tags, None, false).as_deref(), Some("v16.1.1"));
+    }
+
+    #[test]
+    fn ignores_non_version_tags() {
+        let tags: Vec<GitHubTag> = serde_json::from_str(
+            r#"[{ "name": "next", "commit": { "sha": "a" } }, { "name": "latest", "commit": { "sha": "b" } }]"#,
+        ).unwrap();
+        assert_eq!(select(&tags, None, true), None);
+    }
+
+    #[test]
+    fn prefers_v_prefixed_duplicate_deterministically() {
+        let mut tags = recorded_tags();
+        let expected = select(&tags, Some("16.1.1"), false);
+        tags.reverse();
+        assert_eq!(select(&tags, Some("16.1.1"), false), expected);
+        assert_eq!(expected.as_deref(), Some("v16.1.1"));
+    }
+
+    #[test]
+    fn prereleases_require_opt_in() {
+        let tags = recorded_tags();
+        assert_eq!(select(&tags, None, false).as_deref(), Some("v16.1.1"));
+        assert_eq!(select(&tags, None, true).as_deref(), Some("v17.0.0-next.2"));
+    }
+
+    #[test]
+    fn prerelease_opt_in_applies_to_ranges() {
+        let tags = recorded_tags();
+        assert_eq!(select(&tags, Some("^16"), false).as_deref(), Some("v16.1.1"));
+        assert_eq!(select(&tags, Some("^16"), true).as_deref(), Some("v16.2.0-next.1"));
+        assert_eq!(select(&tags, Some("^16.0"), true).as_deref(), Some("v16.2.0-next.1"));
+        assert_eq!(select(&tags, Some(">=15, <17"), true).as_deref(), Some("v16.2.0-next.1"));
+    }
+
+    #[test]
+    fn range_naming_prerelease_matches_it() {
+        let tags = recorded_tags();
+        assert_eq!(select(&tags, Some("^17.0.0-next.0"), false).as_deref(), Some("v17.0.0-next.2"));
+    }
+
+    #[test]
+    fn exact_pins_and_ranges() {
+        let tags = recorded_tags();
+        assert_eq!(select(&tags, Some("16.0.0"), false).as_deref(), Some("v16.0.0"));
+        assert_eq!(select(&tags, Some("v15.0.2"), true).as_deref(), Some("v15.0.2"));
+        assert_eq!(select(&tags, Some("16.2.0"), true), None);
+        assert_eq!(select(&tags, Some("^15"), false).as_deref(), Some("v15.0.2"));
+        assert_eq!(select(&tags, Some("~10.1"), false).as_deref(), Some("v10.1.0"));
+        assert_eq!(select(&tags, Some("^11"), false), None);
+    }
+
+    #[test]
+    fn sends_env_token_only_to_github() {
+        assert!(is_github_url("https://api.github.com/repos/x/y/tags"));
+        assert!(is_github_url("https://raw.githubusercontent.com/x/y/v1/package.json"));
+        assert!(!is_github_url("https://api.github.com.example.com/tags"));
+        assert!(!is_github_url("http://api.github.com/tags"));
+        assert!(!is_github_url("https://mirror.example.com/tags"));
+
+        // An explicit token is meant for the configured endpoints, wherever they are
+        let config = ServerConfig { github_token: Some("config-token".to_string()), ..Default::default() };
+        assert_eq!(github_token(&config, "https://mirror.example.com/tags").as_deref(), Some("config-token"));
+    }
+
+    /// Requests `reply` through `github_get` without retries, returning the error
+    async fn github_error(reply: Reply) -> ServerError {
+        let (url, _) = serve(vec![reply]);
+        let config = ServerConfig {
+            retry: crate::RetryPolicy { max_attempts: 1, ..Default::default() },
+            ..Default::default()
+        };
+        let http = HttpClient::new(&config, &EventSink::default()).unwrap();
+        github_get::<serde_json::Value>(&http, &config, &url).await.unwrap_err()
+    }
+
+    #[tokio::test]
+    async fn maps_rate_limit_responses() {
+        let error = github_error(Reply::new("403 Forbidden", &["X-RateLimit-Remaining: 0", "X-RateLimit-Reset: 1700000000"], b"{}")).await;
+        assert!(matches!(error, ServerError::RateLimited { reset_at: Some(1700000000) }));
+
+        let error = github_error(Reply::new("429 Too Many Requests", &["Retry-After: 60"], b"{}")).await;
+        let ServerError::RateLimited { reset_at: Some(reset_at) } = error else {
+            panic!("expected a rate limit error, got {:?}", error);
+        };
+        assert!(reset_at.abs_diff(unix_now() + 60) <= 5);
+
+        let error = github_error(Reply::new("403 Forbidden", &["X-RateLimit-Remaining: 12"], b"{}")).await;
+        assert!(matches!(error, ServerError::VersionDetectionFailed(_)));
+    }
+
+    #[test]
+    fn accepts_only_full_commit_shas() {
+        assert!(is_commit_sha("863d2581ecda11849ec1d1fdc1f7e6d21c0e1b76"));
+        assert!(is_commit_sha("863D2581ECDA11849EC1D1FDC1F7E6D21C0E1B76"));
+        assert!(!is_commit_sha("863d258"));
+        assert!(!is_commit_sha("../../../../../../../../../../../../../.."));
+        assert!(!is_commit_sha("863d2581ecda11849ec1d1fdc1f7e6d21c0e1b7g"));
+    }
+
+    #[test]
+    fn expands_quality_per_template() {
+        let endpoints = Endpoints::default();
+        assert_eq!(
+            endpoints.download_url("abc", Platform::Win32X64, Quality::Stable),
+            "https://update.code.visualstudio.com/commit:abc/server-win32-x64/archive"
+        );
+        assert_eq!(
+            endpoints.expand("https://mirror.example.com/{commit}/{flavor}/{quality}", "abc", Platform::Win32X64, Quality::Stable),
+            "https://mirror.example.com/abc/server-win32-x64/stable"
+        );
+        assert_eq!(
+            endpoints.download_url("abc", Platform::LinuxX64, Quality::Insider),
+            "https://update.code.visualstudio.com/commit:abc/server-linux-x64/insider"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_requests() {
+        assert!(parse_version_req("not a version").is_err());
+    }
+}
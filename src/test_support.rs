@@ -0,0 +1,55 @@
+// test_support.rs - Helpers shared by the unit tests
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+
+/// A canned HTTP response
+pub(crate) struct Reply {
+    pub(crate) status: &'static str,
+    pub(crate) headers: Vec<String>,
+    pub(crate) body: Vec<u8>,
+}
+
+impl Reply {
+    pub(crate) fn new(status: &'static str, headers: &[&str], body: &[u8]) -> Self {
+        Self {
+            status,
+            headers: headers.iter().map(|header| header.to_string()).collect(),
+            body: body.to_vec(),
+        }
+    }
+}
+
+/// Serves `replies` in order on a local port, returning the server's base URL (without trailing
+/// slash) and the received requests, with lowercased header lines
+pub(crate) fn serve(replies: Vec<Reply>) -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let received = requests.clone();
+    std::thread::spawn(move || {
+        for reply in replies {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request = String::new();
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                    break;
+                }
+                request.push_str(&line.to_ascii_lowercase());
+            }
+            received.lock().unwrap().push(request);
+
+            let mut response = format!("HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n", reply.status, reply.body.len());
+            for header in &reply.headers {
+                response.push_str(&format!("{}\r\n", header));
+            }
+            response.push_str("\r\n");
+            stream.write_all(response.as_bytes()).unwrap();
+            stream.write_all(&reply.body).unwrap();
+        }
+    });
+    (url, requests)
+}